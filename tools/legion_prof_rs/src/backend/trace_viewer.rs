@@ -7,7 +7,7 @@ use std::path::Path;
 use serde::Serialize;
 use serde_json;
 
-use crate::state::{Container, ProcEntryKind, State};
+use crate::state::{Container, ContainerEntry, ProcEntryKind, State};
 
 #[derive(Serialize, Copy, Clone)]
 struct Event<'a> {
@@ -141,3 +141,59 @@ pub fn emit_trace<P: AsRef<Path>>(state: &State, path: P, force: bool) -> io::Re
 
     Ok(())
 }
+
+impl State {
+    // Write every processor entry as a complete event in the Chrome Trace
+    // Event Format, with waits nested inside of the entries that waited.
+    // Unlike emit_trace this does not require sorted time ranges.
+    pub fn write_chrome_trace<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "[")?;
+
+        let mut first = true;
+        let mut emit = |w: &mut W, event: &Event| -> io::Result<()> {
+            if !first {
+                write!(w, ",")?;
+            }
+            first = false;
+            serde_json::to_writer(&mut *w, event)?;
+            Ok(())
+        };
+
+        for proc in self.procs.values() {
+            let category = match proc.kind {
+                Some(kind) => format!("{:?}", kind),
+                None => "Unknown".to_owned(),
+            };
+            for entry in proc.entries() {
+                let name = entry.name(self);
+                let time_range = entry.time_range;
+                let start = time_range.start.unwrap();
+                let event = Event {
+                    name: &name,
+                    category: &category,
+                    phase: "X",
+                    timestamp: start.to_us(),
+                    duration: (time_range.stop.unwrap() - start).to_us(),
+                    pid: proc.proc_id.node_id().0,
+                    tid: proc.proc_id.proc_in_node(),
+                };
+                emit(w, &event)?;
+                for wait in &entry.waiters.wait_intervals {
+                    emit(
+                        w,
+                        &Event {
+                            name: &format!("{} (waiting)", &name),
+                            timestamp: wait.start.to_us(),
+                            duration: (wait.end - wait.start).to_us(),
+                            ..event
+                        },
+                    )?;
+                }
+            }
+        }
+
+        writeln!(w, "]")?;
+
+        Ok(())
+    }
+}
@@ -820,6 +820,64 @@ impl State {
         Ok(())
     }
 
+    // One row per visible proc giving the fraction of each of num_buckets
+    // equal slices of [0, last_time] during which the proc was busy
+    pub fn write_utilization_csv<W: Write>(&self, num_buckets: usize, w: &mut W) -> io::Result<()> {
+        assert!(num_buckets > 0);
+        let end = self.last_time.to_ns();
+        let bounds: Vec<_> = (0..=num_buckets as u64)
+            .map(|i| end * i / num_buckets as u64)
+            .collect();
+
+        let mut f = csv::Writer::from_writer(w);
+        let mut header = vec!["proc".to_owned()];
+        header.extend(bounds[..num_buckets].iter().map(|b| b.to_string()));
+        f.write_record(&header)?;
+
+        for proc in self.procs.values() {
+            if !proc.is_visible() || proc.kind.is_none() {
+                continue;
+            }
+            let mut busy = vec![0; num_buckets];
+            let mut count = 0;
+            let mut busy_start = 0;
+            for point in proc.util_time_points(None) {
+                let time = point.time.to_ns();
+                if point.first {
+                    if count == 0 {
+                        busy_start = time;
+                    }
+                    count += 1;
+                    continue;
+                }
+                count -= 1;
+                if count > 0 {
+                    continue;
+                }
+                for (i, window) in bounds.windows(2).enumerate() {
+                    let start = max(busy_start, window[0]);
+                    let stop = time.min(window[1]);
+                    if start < stop {
+                        busy[i] += stop - start;
+                    }
+                }
+            }
+            let mut row = vec![proc.name(self)];
+            row.extend(busy.iter().zip(bounds.windows(2)).map(|(busy, window)| {
+                let width = window[1] - window[0];
+                let ratio = if width > 0 {
+                    *busy as f64 / width as f64
+                } else {
+                    0.0
+                };
+                ratio.to_string()
+            }));
+            f.write_record(&row)?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+
     fn emit_utilization_tsv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
         let (timepoint_proc, proc_count) = self.group_node_proc_kind_timepoints();
         let timepoint_mem = self.group_node_mem_kind_timepoints();
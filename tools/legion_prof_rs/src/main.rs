@@ -64,6 +64,13 @@ struct ParserArgs {
     )]
     call_threshold: u64,
 
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "hide tasks and meta-tasks shorter than this many nanoseconds and leave them out of utilization"
+    )]
+    min_task_ns: u64,
+
     #[arg(long, help = "a list of nodes that will be visualized")]
     nodes: Option<String>,
 
@@ -76,6 +83,24 @@ struct ParserArgs {
 
     #[arg(short, long, help = "print verbose profiling information")]
     verbose: bool,
+
+    #[arg(
+        long,
+        help = "merge back-to-back GPU kernels of the same variant separated by less than this many microseconds"
+    )]
+    coalesce_kernels: Option<u64>,
+
+    #[arg(
+        long,
+        help = "do not count profiling tasks towards processor utilization (they are still displayed)"
+    )]
+    exclude_prof_tasks: bool,
+
+    #[arg(
+        long,
+        help = "color task variants by a hash of their name so colors are stable across builds"
+    )]
+    color_by_name: bool,
 }
 
 #[derive(Debug, Clone, Args)]
@@ -357,7 +382,9 @@ fn main() -> io::Result<()> {
     }
     for record in records? {
         println!("Matched {} objects", record.len());
-        state.process_records(&record, Timestamp::from_us(args.call_threshold));
+        state
+            .process_records(&record, Timestamp::from_us(args.call_threshold))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
     }
 
     if !state.complete_parse() {
@@ -387,13 +414,22 @@ fn main() -> io::Result<()> {
         have_alllogs = false;
     }
 
-    Config::set_config(filter_input, args.verbose, have_alllogs);
+    Config::set_config(Config {
+        filter_input,
+        verbose: args.verbose,
+        all_logs: have_alllogs,
+        coalesce_kernels: args.coalesce_kernels.map(Timestamp::from_us),
+        exclude_prof_tasks: args.exclude_prof_tasks,
+        color_by_name: args.color_by_name,
+        min_task_ns: args.min_task_ns,
+    });
 
     state.trim_time_range(start_trim, stop_trim);
     println!("Sorting time ranges");
     state.sort_time_range();
     state.check_message_latencies(message_threshold, message_percentage);
     state.filter_output();
+    state.prune_event_graph_to_visible();
     println!("Calculating critical paths");
     state.compute_critical_paths();
 
@@ -1,5 +1,6 @@
-use std::cmp::{Ordering, Reverse, max};
-use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::borrow::Borrow;
+use std::cmp::{Ordering, Reverse, max, min};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque};
 use std::convert::TryFrom;
 use std::fmt;
 use std::num::NonZeroU64;
@@ -11,18 +12,18 @@ use num_enum::TryFromPrimitive;
 
 use rayon::prelude::*;
 
-use petgraph::algo::toposort;
+use petgraph::algo::{tarjan_scc, toposort};
 use petgraph::graph::{Graph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use petgraph::{Directed, Direction};
 
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 
 use slice_group_by::GroupBy;
 
 use crate::backend::common::{CopyInstInfoVec, FillInstInfoVec, InstPretty, SizePretty};
 use crate::num_util::Postincrement;
-use crate::serialize::Record;
+use crate::serialize::{Record, Uuid};
 
 // Make sure this is up to date with lowlevel.h
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, TryFromPrimitive)]
@@ -94,6 +95,20 @@ impl MemKind {
             MemKind::GPUDynamic => "GPU Dynamic",
         }
     }
+
+    pub fn is_device(self) -> bool {
+        matches!(
+            self,
+            MemKind::Framebuffer | MemKind::ZeroCopy | MemKind::GPUManaged | MemKind::GPUDynamic
+        )
+    }
+
+    pub fn is_host(self) -> bool {
+        matches!(
+            self,
+            MemKind::System | MemKind::Registered | MemKind::Socket
+        )
+    }
 }
 
 impl fmt::Display for MemKind {
@@ -106,7 +121,7 @@ impl fmt::Display for MemKind {
 }
 
 // Make sure this is up to date with lowlevel.h
-#[derive(Debug, Copy, Clone, Eq, PartialEq, TryFromPrimitive)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, TryFromPrimitive)]
 #[repr(i32)]
 pub enum DepPartKind {
     Union = 0,
@@ -179,18 +194,38 @@ pub enum DimKind {
     OuterDimR = 27,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LayoutKind {
+    RowMajor,
+    ColumnMajor,
+    Blocked,
+    Unknown,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum DeviceKind {
     Device,
     Host,
 }
 
+// Whether time spent waiting inside a task counts towards utilization
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CountWaitsAs {
+    Idle,
+    Busy,
+}
+
 // the class used to save configurations
 #[derive(Debug, PartialEq)]
 pub struct Config {
-    filter_input: bool,
-    verbose: bool,
-    all_logs: bool,
+    pub filter_input: bool,
+    pub verbose: bool,
+    pub all_logs: bool,
+    pub coalesce_kernels: Option<Timestamp>,
+    pub exclude_prof_tasks: bool,
+    pub color_by_name: bool,
+    // Tasks and meta-tasks shorter than this are hidden by sort_time_range
+    pub min_task_ns: u64,
 }
 
 // CONFIG can be only accessed by Config::name_of_the_member()
@@ -198,12 +233,7 @@ static CONFIG: OnceLock<Config> = OnceLock::new();
 
 impl Config {
     // this function can be only called once, and it will be called in main
-    pub fn set_config(filter_input: bool, verbose: bool, all_logs: bool) {
-        let config = Config {
-            filter_input,
-            verbose,
-            all_logs,
-        };
+    pub fn set_config(config: Config) {
         assert_eq!(CONFIG.set(config), Ok(()));
     }
     // return the singleton of CONFIG, usually we do not need to call it unless
@@ -224,6 +254,22 @@ impl Config {
         let config = Config::global();
         config.all_logs
     }
+    pub fn coalesce_kernels() -> Option<Timestamp> {
+        let config = Config::global();
+        config.coalesce_kernels
+    }
+    pub fn exclude_prof_tasks() -> bool {
+        let config = Config::global();
+        config.exclude_prof_tasks
+    }
+    pub fn color_by_name() -> bool {
+        let config = Config::global();
+        config.color_by_name
+    }
+    pub fn min_task_ns() -> u64 {
+        let config = Config::global();
+        config.min_task_ns
+    }
 }
 
 #[macro_export]
@@ -241,9 +287,29 @@ macro_rules! conditional_assert {
     )
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, From)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimeUnit {
+    Ns,
+    Us,
+    Ms,
+    S,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, From)]
 pub struct Timestamp(NonMaxU64 /* ns */);
 
+// The derived impl already produced a bare nanosecond count through the
+// newtype, this just spells out the format instead of relying on how
+// NonMaxU64 happens to serialize
+impl Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.to_ns())
+    }
+}
+
 impl Timestamp {
     pub const MAX: Timestamp = Timestamp(NonMaxU64::MAX);
     pub const MIN: Timestamp = Timestamp(NonMaxU64::ZERO);
@@ -261,6 +327,23 @@ impl Timestamp {
     pub const fn to_ns(&self) -> u64 {
         self.0.get()
     }
+    pub fn saturating_sub(self, rhs: Timestamp) -> Timestamp {
+        Timestamp::from_ns(self.to_ns().saturating_sub(rhs.to_ns()))
+    }
+    pub fn format_as(&self, unit: TimeUnit) -> String {
+        let nanoseconds = self.to_ns();
+        let (divisor, digits) = match unit {
+            TimeUnit::Ns => return nanoseconds.to_string(),
+            TimeUnit::Us => (1_000, 3),
+            TimeUnit::Ms => (1_000_000, 6),
+            TimeUnit::S => (1_000_000_000, 9),
+        };
+        format!(
+            "{}.{:0>digits$}",
+            nanoseconds / divisor,
+            nanoseconds % divisor
+        )
+    }
 }
 
 impl std::ops::Add for Timestamp {
@@ -342,6 +425,29 @@ where
     }
 }
 
+// Number of levels the stacking sweep would need for these points. Freed
+// levels are always reused first, so this is the peak number of live
+// entries. Points are sorted the same way the containers sort them.
+pub fn compute_max_levels<E, S>(points: &[TimePoint<E, S>]) -> u32
+where
+    E: std::marker::Copy,
+    S: std::marker::Copy + Ord,
+{
+    let mut sorted: Vec<_> = points.iter().map(|point| point.time_key()).collect();
+    sorted.sort();
+    let mut live = 0;
+    let mut max_levels = 0;
+    for (_, first, _) in sorted {
+        if first == 0 {
+            live += 1;
+            max_levels = max(max_levels, live);
+        } else {
+            live -= 1;
+        }
+    }
+    max_levels
+}
+
 // Common methods that apply to Proc, Mem, Chan
 pub trait Container {
     type E: std::marker::Copy + std::fmt::Debug;
@@ -448,6 +554,18 @@ impl ProcEntry {
     fn trim_time_range(&mut self, start: Timestamp, stop: Timestamp) -> bool {
         self.time_range.trim_time_range(start, stop)
     }
+
+    // Time spent in this entry itself, excluding any runtime/mapper calls
+    // made from it (event waits still count towards it)
+    pub fn self_time(&self) -> Timestamp {
+        let mut time = self.time_range.stop.unwrap() - self.time_range.start.unwrap();
+        for wait in &self.waiters.wait_intervals {
+            if wait.callee.is_some() {
+                time -= wait.end - wait.start;
+            }
+        }
+        time
+    }
 }
 
 impl ContainerEntry for ProcEntry {
@@ -702,6 +820,42 @@ impl Proc {
         self
     }
 
+    // Merge back-to-back GPU kernels of the same variant on the device
+    // timeline into a single entry. Returns the entries that were removed.
+    // Every GPU task launches its own kernel, so this intentionally merges
+    // kernels from different tasks: the merged entry keeps the op and creator
+    // of the first kernel, while each task still has its own entry on the
+    // host timeline for attribution.
+    pub fn coalesce_device_kernels(&mut self, max_gap: Timestamp) -> Vec<ProfUID> {
+        let mut kernels: Vec<_> = self
+            .entries
+            .values()
+            .filter(|entry| matches!(entry.kind, ProcEntryKind::GPUKernel(..)))
+            .map(|entry| (entry.time_range.start.unwrap(), entry.base.prof_uid))
+            .collect();
+        kernels.sort();
+
+        let mut removed = Vec::new();
+        let mut current: Option<ProfUID> = None;
+        for (start, prof_uid) in kernels {
+            if let Some(current_uid) = current {
+                let (kind, stop) = {
+                    let entry = self.entry(current_uid);
+                    (entry.kind, entry.time_range.stop.unwrap())
+                };
+                if kind == self.entry(prof_uid).kind && start < stop + max_gap {
+                    let merged = self.entries.remove(&prof_uid).unwrap();
+                    let entry = self.entry_mut(current_uid);
+                    entry.time_range.stop = Some(max(stop, merged.time_range.stop.unwrap()));
+                    removed.push(prof_uid);
+                    continue;
+                }
+            }
+            current = Some(prof_uid);
+        }
+        removed
+    }
+
     pub fn find_task(&self, op_id: OpID) -> Option<&ProcEntry> {
         let prof_uid = self.tasks.get(&op_id)?;
         self.entries.get(prof_uid)
@@ -730,6 +884,21 @@ impl Proc {
         self.entries.get(&prof_uid)
     }
 
+    // Only meaningful after sort_calls_and_waits has nested the calls
+    pub fn subcalls_of(&self, prof_uid: ProfUID) -> Vec<ProfUID> {
+        let Some(entry) = self.entries.get(&prof_uid) else {
+            return Vec::new();
+        };
+        let mut calls: Vec<_> = entry
+            .waiters
+            .wait_intervals
+            .iter()
+            .filter_map(|wait| Some((wait.start, wait.callee?)))
+            .collect();
+        calls.sort();
+        calls.into_iter().map(|(_, callee)| callee).collect()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
@@ -738,8 +907,17 @@ impl Proc {
         self.entries.values()
     }
 
-    fn trim_time_range(&mut self, start: Timestamp, stop: Timestamp) {
+    fn trim_time_range(&mut self, start: Timestamp, stop: Timestamp) -> usize {
+        let before = self.entries.len();
         self.entries.retain(|_, t| !t.trim_time_range(start, stop));
+        before - self.entries.len()
+    }
+
+    fn rebase(&mut self, offset: Timestamp) {
+        for entry in self.entries.values_mut() {
+            entry.time_range.rebase(offset);
+            entry.waiters.rebase(offset);
+        }
     }
 
     fn update_prof_task_times(
@@ -892,11 +1070,18 @@ impl Proc {
     }
 
     fn sort_time_range(&mut self) {
+        self.sort_time_range_impl(Config::exclude_prof_tasks());
+    }
+
+    // Profiling tasks are always displayed in the timeline, but when
+    // exclude_prof_tasks is set they do not count towards utilization
+    fn sort_time_range_impl(&mut self, exclude_prof_tasks: bool) {
         fn add(
             time: &TimeRange,
             prof_uid: ProfUID,
             points: &mut Vec<ProcPoint>,
             util_points: &mut Vec<ProcPoint>,
+            count_util: bool,
         ) {
             let start = time.start.unwrap();
             let stop = time.stop.unwrap();
@@ -904,6 +1089,9 @@ impl Proc {
             points.push(ProcPoint::new(start, prof_uid, true, Timestamp::MAX - stop));
             points.push(ProcPoint::new(stop, prof_uid, false, Timestamp::ZERO));
 
+            if !count_util {
+                return;
+            }
             util_points.push(ProcPoint::new(start, prof_uid, true, Timestamp::MAX - stop));
             util_points.push(ProcPoint::new(stop, prof_uid, false, Timestamp::ZERO));
         }
@@ -936,20 +1124,33 @@ impl Proc {
                 let time = &entry.time_range;
                 match entry.kind {
                     ProcEntryKind::GPUKernel(_, _) => {
-                        add(time, *uid, &mut points_device, &mut util_points_device);
+                        add(
+                            time,
+                            *uid,
+                            &mut points_device,
+                            &mut util_points_device,
+                            true,
+                        );
                         add_waiters(&entry.waiters, *uid, &mut util_points_device);
                     }
                     _ => {
-                        add(time, *uid, &mut points, &mut util_points);
-                        add_waiters(&entry.waiters, *uid, &mut util_points);
+                        let count_util =
+                            !(exclude_prof_tasks && entry.kind == ProcEntryKind::ProfTask);
+                        add(time, *uid, &mut points, &mut util_points, count_util);
+                        if count_util {
+                            add_waiters(&entry.waiters, *uid, &mut util_points);
+                        }
                     }
                 }
             }
         } else {
             for (uid, entry) in &self.entries {
                 let time = &entry.time_range;
-                add(time, *uid, &mut points, &mut util_points);
-                add_waiters(&entry.waiters, *uid, &mut util_points);
+                let count_util = !(exclude_prof_tasks && entry.kind == ProcEntryKind::ProfTask);
+                add(time, *uid, &mut points, &mut util_points, count_util);
+                if count_util {
+                    add_waiters(&entry.waiters, *uid, &mut util_points);
+                }
             }
         }
 
@@ -999,6 +1200,62 @@ impl Proc {
         self.util_time_points_device = util_points_device;
     }
 
+    // Hide tasks and meta-tasks shorter than min_duration, along with the
+    // runtime/mapper calls and GPU kernels that belong to them. Unlike other
+    // hidden entries these are dropped from the utilization as well.
+    fn hide_short_entries(&mut self, min_duration: Timestamp) {
+        let short: BTreeSet<_> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                matches!(
+                    entry.kind,
+                    ProcEntryKind::Task(..) | ProcEntryKind::MetaTask(_)
+                ) && entry.time_range.stop.unwrap() - entry.time_range.start.unwrap() < min_duration
+            })
+            .map(|(uid, _)| *uid)
+            .collect();
+        if short.is_empty() {
+            return;
+        }
+        let owner = |mut uid: ProfUID| loop {
+            let entry = &self.entries[&uid];
+            let owned = matches!(
+                entry.kind,
+                ProcEntryKind::MapperCall(..)
+                    | ProcEntryKind::RuntimeCall(_)
+                    | ProcEntryKind::ApplicationCall(_)
+                    | ProcEntryKind::GPUKernel(..)
+            );
+            match entry.creator {
+                Some(creator) if owned && self.entries.contains_key(&creator) => uid = creator,
+                _ => return uid,
+            }
+        };
+        let hidden: Vec<_> = self
+            .entries
+            .keys()
+            .filter(|uid| short.contains(&owner(**uid)))
+            .copied()
+            .collect();
+        for uid in hidden {
+            self.entries.get_mut(&uid).unwrap().base.hidden = true;
+        }
+        self.drop_hidden_time_points();
+        let entries = &self.entries;
+        self.util_time_points
+            .retain(|p| !entries[&p.entry].base.hidden);
+        self.util_time_points_device
+            .retain(|p| !entries[&p.entry].base.hidden);
+    }
+
+    fn drop_hidden_time_points(&mut self) {
+        let entries = &self.entries;
+        self.time_points.retain(|p| !entries[&p.entry].base.hidden);
+        self.time_points_device
+            .retain(|p| !entries[&p.entry].base.hidden);
+    }
+
     fn stack_time_points(&mut self) {
         let mut time_points = Vec::new();
         std::mem::swap(&mut time_points, &mut self.time_points);
@@ -1041,6 +1298,178 @@ impl Proc {
         }
         result
     }
+
+    pub fn entry_kind_counts(&self) -> BTreeMap<&'static str, u64> {
+        let mut counts = BTreeMap::new();
+        for entry in self.entries.values() {
+            let label = match entry.kind {
+                ProcEntryKind::Task(..) => "Task",
+                ProcEntryKind::MetaTask(_) => "MetaTask",
+                ProcEntryKind::MapperCall(..) => "MapperCall",
+                ProcEntryKind::RuntimeCall(_) => "RuntimeCall",
+                ProcEntryKind::ApplicationCall(_) => "ApplicationCall",
+                ProcEntryKind::GPUKernel(..) => "GPUKernel",
+                ProcEntryKind::ProfTask => "ProfTask",
+            };
+            *counts.entry(label).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    pub fn runtime_call_time(&self) -> Timestamp {
+        let mut total = Timestamp::ZERO;
+        for entry in self.entries.values() {
+            if let ProcEntryKind::RuntimeCall(_) = entry.kind {
+                total += entry.time_range.stop.unwrap() - entry.time_range.start.unwrap();
+            }
+        }
+        total
+    }
+
+    // Subcalls also show up as wait intervals but are not blocking
+    pub fn total_wait_time(&self) -> Timestamp {
+        let mut total = Timestamp::ZERO;
+        for entry in self.entries.values() {
+            for wait in &entry.waiters.wait_intervals {
+                if wait.callee.is_none() {
+                    total += wait.end - wait.start;
+                }
+            }
+        }
+        total
+    }
+
+    pub fn meta_vs_app_time(&self) -> (Timestamp, Timestamp) {
+        let mut meta = Timestamp::ZERO;
+        let mut app = Timestamp::ZERO;
+        for entry in self.entries.values() {
+            // Runtime and mapper calls run inside tasks and GPU kernels are
+            // mirrored by their host task, so neither is counted separately
+            let total = if entry.is_meta() {
+                &mut meta
+            } else if matches!(entry.kind, ProcEntryKind::Task(..)) {
+                &mut app
+            } else {
+                continue;
+            };
+            let mut active = entry.time_range.stop.unwrap() - entry.time_range.start.unwrap();
+            for wait in &entry.waiters.wait_intervals {
+                if wait.callee.is_none() {
+                    active -= wait.end - wait.start;
+                }
+            }
+            *total += active;
+        }
+        (meta, app)
+    }
+
+    // Busy (1.0) / idle (0.0) steps of the processor over time, only valid
+    // after sort_time_range
+    pub fn utilization_series(
+        &self,
+        device: Option<DeviceKind>,
+        waits: CountWaitsAs,
+    ) -> Vec<(Timestamp, f64)> {
+        // Each wait contributes a stop point at its start and a start point
+        // at its end. Points that agree on time, entry and direction are
+        // interchangeable, so removing one of each is enough to undo a wait.
+        let mut skip: BTreeMap<(Timestamp, ProfUID, bool), usize> = BTreeMap::new();
+        if waits == CountWaitsAs::Busy {
+            for (uid, entry) in &self.entries {
+                for wait in &entry.waiters.wait_intervals {
+                    *skip.entry((wait.start, *uid, false)).or_insert(0) += 1;
+                    *skip.entry((wait.end, *uid, true)).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut series: Vec<(Timestamp, f64)> = Vec::new();
+        let mut count = 0;
+        for point in self.util_time_points(device) {
+            if let Some(remaining) = skip.get_mut(&(point.time, point.entry, point.first)) {
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    continue;
+                }
+            }
+            if point.first {
+                count += 1;
+            } else {
+                count -= 1;
+            }
+            let busy = if count > 0 { 1.0 } else { 0.0 };
+            match series.last_mut() {
+                Some(last) if last.0 == point.time => last.1 = busy,
+                _ => series.push((point.time, busy)),
+            }
+        }
+        series.dedup_by(|b, a| a.1 == b.1);
+        series
+    }
+
+    // Time during which something other than an event wait was running on
+    // the timeline, only valid after sort_time_range
+    pub fn busy_time(&self, device: Option<DeviceKind>) -> Timestamp {
+        let series = self.utilization_series(device, CountWaitsAs::Idle);
+        let mut busy = Timestamp::ZERO;
+        for step in series.windows(2) {
+            if step[0].1 > 0.0 {
+                busy += step[1].0 - step[0].0;
+            }
+        }
+        busy
+    }
+
+    // Time during which a level 0 entry is blocked on an event and nothing
+    // else is running on the timeline, only valid after sort_time_range
+    pub fn wait_attributed_idle(&self, device: Option<DeviceKind>) -> Timestamp {
+        // GPU kernels live on the device timeline, everything else on the host
+        let want_device = device == Some(DeviceKind::Device);
+
+        // Waits with a callee are subcalls, which are not idle time
+        let mut waits = Vec::new();
+        for entry in self.entries.values() {
+            if matches!(entry.kind, ProcEntryKind::GPUKernel(..)) != want_device
+                || entry.base.level != Some(0)
+            {
+                continue;
+            }
+            for wait in &entry.waiters.wait_intervals {
+                if wait.callee.is_none() {
+                    waits.push((wait.start, 1));
+                    waits.push((wait.end, -1));
+                }
+            }
+        }
+        waits.sort();
+
+        // Sweep the utilization points (where waits are already inverted)
+        // together with the waits, counting the time where something is
+        // waiting but nothing is running
+        let mut points = self.util_time_points(device).iter().peekable();
+        let mut waits = waits.into_iter().peekable();
+        let (mut running, mut waiting) = (0i64, 0i64);
+        let mut last = Timestamp::ZERO;
+        let mut idle = Timestamp::ZERO;
+        loop {
+            let time = match (points.peek(), waits.peek()) {
+                (Some(point), Some(wait)) => min(point.time, wait.0),
+                (Some(point), None) => point.time,
+                (None, Some(wait)) => wait.0,
+                (None, None) => break,
+            };
+            if waiting > 0 && running == 0 {
+                idle += time - last;
+            }
+            last = time;
+            while let Some(point) = points.next_if(|p| p.time == time) {
+                running += if point.first { 1 } else { -1 };
+            }
+            while let Some((_, delta)) = waits.next_if(|w| w.0 == time) {
+                waiting += delta;
+            }
+        }
+        idle
+    }
 }
 
 impl Container for Proc {
@@ -1238,8 +1667,74 @@ impl Mem {
         self.insts.is_empty()
     }
 
-    fn trim_time_range(&mut self, start: Timestamp, stop: Timestamp) {
+    pub fn live_bytes_at(&self, time: Timestamp) -> u64 {
+        self.insts
+            .values()
+            .filter(|inst| {
+                inst.time_range.ready.is_some_and(|ready| ready <= time)
+                    && inst.time_range.stop.is_some_and(|stop| time < stop)
+            })
+            .filter_map(|inst| inst.size)
+            .sum()
+    }
+
+    // Fraction of instances that had to wait after being allocated before
+    // they became ready, a sign that allocations were contending
+    pub fn allocation_contention_score(&self) -> f64 {
+        if self.insts.is_empty() {
+            return 0.0;
+        }
+        let deferred = self
+            .insts
+            .values()
+            .filter(|inst| !inst.allocated_immediately())
+            .count();
+        deferred as f64 / self.insts.len() as f64
+    }
+
+    pub fn write_instances_json<W: std::io::Write>(
+        &self,
+        state: &State,
+        w: &mut W,
+    ) -> std::io::Result<()> {
+        #[derive(Serialize)]
+        struct Instance {
+            prof_uid: u64,
+            name: String,
+            size: Option<u64>,
+            level: Option<u32>,
+            create: Option<Timestamp>,
+            ready: Option<Timestamp>,
+            destroy: Option<Timestamp>,
+        }
+
+        let instances: Vec<_> = self
+            .insts
+            .values()
+            .map(|inst| Instance {
+                prof_uid: inst.base.prof_uid.0,
+                name: InstPretty(inst, state).to_string(),
+                size: inst.size,
+                level: inst.base.level,
+                create: inst.time_range.create,
+                ready: inst.time_range.ready,
+                destroy: inst.time_range.stop,
+            })
+            .collect();
+        serde_json::to_writer(w, &instances)?;
+        Ok(())
+    }
+
+    fn trim_time_range(&mut self, start: Timestamp, stop: Timestamp) -> usize {
+        let before = self.insts.len();
         self.insts.retain(|_, i| !i.trim_time_range(start, stop));
+        before - self.insts.len()
+    }
+
+    fn rebase(&mut self, offset: Timestamp) {
+        for inst in self.insts.values_mut() {
+            inst.time_range.rebase(offset);
+        }
     }
 
     fn calculate_dynamic_memory_size(&self, points: &Vec<MemPoint>) -> u64 {
@@ -1310,6 +1805,11 @@ impl Mem {
         }
     }
 
+    fn drop_hidden_time_points(&mut self) {
+        let insts = &self.insts;
+        self.time_points.retain(|p| !insts[&p.entry].base.hidden);
+    }
+
     fn stack_time_points(&mut self) {
         let mut time_points = Vec::new();
         std::mem::swap(&mut time_points, &mut self.time_points);
@@ -1319,6 +1819,20 @@ impl Mem {
     pub fn is_visible(&self) -> bool {
         self.visible
     }
+
+    // Rough estimate of the space that was never used in this memory, based
+    // on the allocation points so it is only meaningful after sort_time_range.
+    // Note that for memories without a known capacity (or dynamic GPU
+    // memories) the capacity is derived from the instances themselves, so
+    // this will always be close to zero for those.
+    pub fn fragmentation(&self) -> f64 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+        let peak = self.calculate_dynamic_memory_size(&self.util_time_points);
+        let used = peak as f64 / self.capacity as f64;
+        (1.0 - used).clamp(0.0, 1.0)
+    }
 }
 
 impl Container for Mem {
@@ -1625,8 +2139,16 @@ impl Chan {
         self.entries.is_empty()
     }
 
-    fn trim_time_range(&mut self, start: Timestamp, stop: Timestamp) {
+    fn trim_time_range(&mut self, start: Timestamp, stop: Timestamp) -> usize {
+        let before = self.entries.len();
         self.entries.retain(|_, e| !e.trim_time_range(start, stop));
+        before - self.entries.len()
+    }
+
+    fn rebase(&mut self, offset: Timestamp) {
+        for entry in self.entries.values_mut() {
+            entry.time_range_mut().rebase(offset);
+        }
     }
 
     fn sort_time_range(&mut self) {
@@ -1666,6 +2188,12 @@ impl Chan {
         self.util_time_points = points;
     }
 
+    fn drop_hidden_time_points(&mut self) {
+        let entries = &self.entries;
+        self.time_points
+            .retain(|p| !entries[&p.entry].base().hidden);
+    }
+
     fn stack_time_points(&mut self) {
         let mut time_points = Vec::new();
         std::mem::swap(&mut time_points, &mut self.time_points);
@@ -1675,6 +2203,66 @@ impl Chan {
     pub fn is_visible(&self) -> bool {
         self.visible
     }
+
+    // Fraction of [0, total] during which the channel had at least one
+    // entry in flight, only valid after sort_time_range
+    pub fn busy_fraction(&self, total: Timestamp) -> f64 {
+        if total == Timestamp::ZERO {
+            return 0.0;
+        }
+        let mut active = 0;
+        let mut busy_start = Timestamp::ZERO;
+        let mut busy = Timestamp::ZERO;
+        for point in &self.util_time_points {
+            let time = min(point.time, total);
+            if point.first {
+                if active == 0 {
+                    busy_start = time;
+                }
+                active += 1;
+            } else {
+                active -= 1;
+                if active == 0 {
+                    busy += time - busy_start;
+                }
+            }
+        }
+        busy.to_ns() as f64 / total.to_ns() as f64
+    }
+
+    pub fn copy_kind_counts(&self) -> BTreeMap<CopyKind, u64> {
+        let mut counts = BTreeMap::new();
+        for entry in self.entries.values() {
+            if let ChanEntry::Copy(copy) = entry {
+                if let Some(kind) = copy.copy_kind {
+                    *counts.entry(kind).or_insert(0) += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    // Time-weighted average number of entries in flight over [0, total],
+    // only valid after sort_time_range
+    pub fn average_queue_depth(&self, total: Timestamp) -> f64 {
+        if total == Timestamp::ZERO {
+            return 0.0;
+        }
+        let mut active: u64 = 0;
+        let mut last = Timestamp::ZERO;
+        let mut area: u64 = 0;
+        for point in &self.util_time_points {
+            let time = min(point.time, total);
+            area += active * (time - last).to_ns();
+            last = time;
+            if point.first {
+                active += 1;
+            } else {
+                active -= 1;
+            }
+        }
+        area as f64 / total.to_ns() as f64
+    }
 }
 
 impl Container for Chan {
@@ -2063,6 +2651,17 @@ impl Inst {
         self.time_range.stop = Some(destroy);
         self
     }
+    // set_start_stop skips the ordering checks of TimeRange::new_full
+    pub fn validate_times(&self) -> bool {
+        let TimeRange {
+            create,
+            ready,
+            stop,
+            ..
+        } = self.time_range;
+        !(create.zip(ready).is_some_and(|(c, r)| c > r)
+            || ready.zip(stop).is_some_and(|(r, s)| r > s))
+    }
     fn set_allocated(&mut self, allocated: Timestamp) -> &mut Self {
         self.time_range.spawn = Some(allocated);
         self
@@ -2122,6 +2721,36 @@ impl Inst {
         self.creator = Some(creator);
         self
     }
+    pub fn layout_kind(&self) -> LayoutKind {
+        // Any split dimensions means the layout is tiled
+        if self
+            .dim_order
+            .values()
+            .any(|d| *d as u32 > DimKind::DimF as u32)
+        {
+            return LayoutKind::Blocked;
+        }
+        // Ignore the position of the fields, we only care about the order of
+        // the spatial dimensions from fastest to slowest varying
+        let dims: Vec<_> = self
+            .dim_order
+            .values()
+            .filter(|d| **d != DimKind::DimF)
+            .map(|d| *d as u32)
+            .collect();
+        if dims.is_empty() {
+            return LayoutKind::Unknown;
+        }
+        let last = dims.len() as u32 - 1;
+        // For 1-D layouts both orders are the same, report them as column major
+        if dims.iter().zip(0..).all(|(d, i)| *d == i) {
+            LayoutKind::ColumnMajor
+        } else if dims.iter().zip(0..).all(|(d, i)| *d == last - i) {
+            LayoutKind::RowMajor
+        } else {
+            LayoutKind::Unknown
+        }
+    }
     pub fn allocated_immediately(&self) -> bool {
         // Remember that 'spawn' is really the 'allocated' response time
         if let Some(allocated) = self.time_range.spawn {
@@ -2395,6 +3024,8 @@ pub struct ProfUID(pub u64);
 pub struct Base {
     pub prof_uid: ProfUID,
     pub level: Option<u32>,
+    // Hidden entries keep their level but are left out of the time points
+    pub hidden: bool,
 }
 
 impl Base {
@@ -2402,12 +3033,14 @@ impl Base {
         Base {
             prof_uid: allocator.create_fresh(),
             level: None,
+            hidden: false,
         }
     }
     fn from_fevent(allocator: &mut ProfUIDAllocator, fevent: EventID) -> Self {
         Base {
             prof_uid: allocator.create_object(fevent),
             level: None,
+            hidden: false,
         }
     }
     fn set_level(&mut self, level: u32) -> &mut Self {
@@ -2500,6 +3133,14 @@ impl TimeRange {
         self.stop = self.stop.map(clip);
         false
     }
+    fn rebase(&mut self, offset: Timestamp) {
+        let shift = |value: Timestamp| value.saturating_sub(offset);
+        self.spawn = self.spawn.map(shift);
+        self.create = self.create.map(shift);
+        self.ready = self.ready.map(shift);
+        self.start = self.start.map(shift);
+        self.stop = self.stop.map(shift);
+    }
 }
 
 #[derive(Debug)]
@@ -2561,6 +3202,13 @@ impl Waiters {
         self.wait_intervals.push(interval);
         self
     }
+    fn rebase(&mut self, offset: Timestamp) {
+        for wait in &mut self.wait_intervals {
+            wait.start = wait.start.saturating_sub(offset);
+            wait.ready = wait.ready.saturating_sub(offset);
+            wait.end = wait.end.saturating_sub(offset);
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
@@ -2604,18 +3252,24 @@ impl OpKind {
 #[derive(Debug)]
 pub struct OperationInstInfo {
     pub inst_uid: ProfUID,
-    _index: u32,
-    _field_id: FieldID,
+    index: u32,
+    field_id: FieldID,
 }
 
 impl OperationInstInfo {
     fn new(inst_uid: ProfUID, index: u32, field_id: FieldID) -> Self {
         OperationInstInfo {
             inst_uid,
-            _index: index,
-            _field_id: field_id,
+            index,
+            field_id,
         }
     }
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+    pub fn field_id(&self) -> FieldID {
+        self.field_id
+    }
 }
 
 #[derive(Debug)]
@@ -2733,6 +3387,14 @@ impl CopyInstInfo {
             indirect,
         }
     }
+
+    pub fn src_mem(&self) -> Option<MemID> {
+        self.src
+    }
+
+    pub fn dst_mem(&self) -> Option<MemID> {
+        self.dst
+    }
 }
 
 #[derive(Debug)]
@@ -2777,6 +3439,30 @@ impl Copy {
         self.copy_inst_infos.push(copy_inst_info);
     }
 
+    // Bytes per nanosecond, or None if the copy took no measurable time
+    pub fn bandwidth(&self) -> Option<f64> {
+        let duration = self.time_range.stop? - self.time_range.start?;
+        if duration == Timestamp::ZERO {
+            return None;
+        }
+        Some(self.size as f64 / duration.to_ns() as f64)
+    }
+
+    pub fn endpoints(&self) -> (Option<MemID>, Option<MemID>) {
+        match self.copy_inst_infos.first() {
+            Some(info) => (info.src_mem(), info.dst_mem()),
+            None => (None, None),
+        }
+    }
+
+    pub fn queue_time(&self) -> Timestamp {
+        self.time_range.start.unwrap() - self.time_range.ready.unwrap()
+    }
+
+    pub fn transfer_time(&self) -> Timestamp {
+        self.time_range.stop.unwrap() - self.time_range.start.unwrap()
+    }
+
     fn split_by_channel(
         self,
         allocator: &mut ProfUIDAllocator,
@@ -2963,6 +3649,22 @@ impl DepPart {
     }
 }
 
+const CALIBRATION_ERR_THRESHOLD_NS: u64 = 1000;
+
+// The palette size must not depend on the profile, otherwise the same name
+// would map to different colors in different runs
+const NAME_COLOR_STEPS: u32 = 1 << 12;
+
+fn compute_name_color(name: &str) -> Color {
+    // FNV-1a, so the result does not depend on the std hasher
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    compute_color(hash % NAME_COLOR_STEPS, NAME_COLOR_STEPS)
+}
+
 fn compute_color(step: u32, num_steps: u32) -> Color {
     assert!(step <= num_steps);
     let h = (step as f64) / (num_steps as f64);
@@ -3098,6 +3800,9 @@ impl ProfUIDAllocator {
     fn find_fevent(&self, prof_uid: ProfUID) -> EventID {
         *self.reverse_lookup.get(&prof_uid).unwrap()
     }
+    fn try_find_fevent(&self, prof_uid: ProfUID) -> Option<EventID> {
+        self.reverse_lookup.get(&prof_uid).copied()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -3127,38 +3832,40 @@ impl RuntimeConfig {
             || self.bounds_checks
             || self.resilient
     }
+
+    // The names of the active flags, in the order they are displayed
+    pub fn describe(&self) -> Vec<&'static str> {
+        [
+            (self.debug, "Debug Mode"),
+            (self.spy, "Legion Spy"),
+            (self.gc, "Legion GC"),
+            (self.inorder, "-lg:inorder"),
+            (self.safe_mapper && !self.debug, "-lg:safe_mapper"),
+            (self.safe_runtime && !self.debug, "Safe Runtime"),
+            (self.safe_ctrlrepl, "-lg:safe_ctrlrepl"),
+            (self.part_checks, "-lg:partcheck"),
+            (self.bounds_checks, "Bounds Checks"),
+            (self.resilient, "Resilience"),
+        ]
+        .into_iter()
+        .filter(|(cond, _)| *cond)
+        .map(|(_, name)| name)
+        .collect()
+    }
 }
 
 impl fmt::Display for RuntimeConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut first = true;
-        let mut conf = |cond, name| {
-            if cond {
-                if !first {
-                    write!(f, ", ")?;
-                }
-                write!(f, "{}", name)?;
-                first = false;
-            }
-            Ok(())
-        };
-
-        conf(self.debug, "Debug Mode")?;
-        conf(self.spy, "Legion Spy")?;
-        conf(self.gc, "Legion GC")?;
-        conf(self.inorder, "-lg:inorder")?;
-        conf(self.safe_mapper && !self.debug, "-lg:safe_mapper")?;
-        conf(self.safe_runtime && !self.debug, "Safe Runtime")?;
-        conf(self.safe_ctrlrepl, "-lg:safe_ctrlrepl")?;
-        conf(self.part_checks, "-lg:partcheck")?;
-        conf(self.bounds_checks, "Bounds Checks")?;
-        conf(self.resilient, "Resilience")
+        write!(f, "{}", self.describe().join(", "))
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct BacktraceID(pub u64);
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ReservationID(pub u64);
+
 // Enum for describing the kinds of event nodes the graph
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum EventEntryKind {
@@ -3211,6 +3918,62 @@ impl EventEntry {
 
 type CriticalPathGraph = Graph<EventEntry, (), Directed, usize>;
 
+// Wellford's algorithm for online variance calculation
+#[derive(Debug, Default, Copy, Clone)]
+pub struct OnlineVariance {
+    pub count: u64,
+    pub mean: f64,
+    m2: f64,
+}
+
+impl OnlineVariance {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        let delta2 = value - self.mean;
+        self.m2 += delta * delta2;
+    }
+
+    // Combine two sets of samples (Chan et al.'s parallel algorithm)
+    fn merge(&mut self, other: &OnlineVariance) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let (n1, n2, n) = (self.count as f64, other.count as f64, count as f64);
+        self.mean += delta * n2 / n;
+        self.m2 += other.m2 + delta * delta * n1 * n2 / n;
+        self.count = count;
+    }
+
+    pub fn stddev(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.m2 / self.count as f64).sqrt()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MessageLatencyStats {
+    pub total_messages: usize,
+    pub skewed_messages: usize,
+    pub total_skew: Timestamp,
+    // Skew in ns between (creator node, execution node) pairs
+    pub skew_nodes: BTreeMap<(NodeID, NodeID), OnlineVariance>,
+    // Latency statistics are in us, same as the message threshold
+    pub mean_latency_us: f64,
+    pub stddev_latency_us: f64,
+    pub max_latency_us: f64,
+    pub latencies_us: Vec<f64>,
+}
+
 #[derive(Debug, Default)]
 pub struct State {
     prof_uid_allocator: ProfUIDAllocator,
@@ -3218,7 +3981,7 @@ pub struct State {
     pub num_nodes: u32,
     pub runtime_config: RuntimeConfig,
     pub zero_time: TimestampDelta,
-    pub _calibration_err: i64,
+    pub calibration_err: i64,
     pub procs: BTreeMap<ProcID, Proc>,
     pub mems: BTreeMap<MemID, Mem>,
     pub mem_proc_affinity: BTreeMap<MemID, MemProcAffinity>,
@@ -3250,6 +4013,7 @@ pub struct State {
     pub backtraces: BTreeMap<BacktraceID, String>,
     pub event_graph: CriticalPathGraph,
     pub event_lookup: BTreeMap<EventID, CriticalPathVertex>,
+    pub reservations: BTreeMap<ReservationID, Vec<EventID>>,
 }
 
 impl State {
@@ -3261,36 +4025,274 @@ impl State {
         self.operations.get(&op_id)
     }
 
-    fn find_op_mut(&mut self, op_id: OpID) -> Option<&mut Operation> {
-        self.operations.get_mut(&op_id)
+    pub fn children_of(&self, op_id: OpID) -> Vec<OpID> {
+        self.operations
+            .iter()
+            .filter(|(_, op)| op.parent_id == Some(op_id))
+            .map(|(child_id, _)| *child_id)
+            .collect()
     }
 
-    fn find_op_provenance(&self, op_id: OpID) -> Option<&str> {
-        self.find_op(op_id)
-            .and_then(|op| op.provenance.and_then(|pid| self.find_provenance(pid)))
+    pub fn instances_used_by(&self, op_id: OpID) -> Vec<ProfUID> {
+        let Some(op) = self.find_op(op_id) else {
+            return Vec::new();
+        };
+        let mut seen = BTreeSet::new();
+        op.operation_inst_infos
+            .iter()
+            .map(|info| info.inst_uid)
+            .filter(|inst_uid| seen.insert(*inst_uid))
+            .collect()
     }
 
-    fn create_fevent_reference(&mut self, fevent: EventID) -> ProfUID {
-        self.prof_uid_allocator.create_reference(fevent)
+    pub fn op_count(&self) -> usize {
+        self.operations.len()
     }
 
-    pub fn find_fevent(&self, prof_uid: ProfUID) -> EventID {
-        self.prof_uid_allocator.find_fevent(prof_uid)
+    pub fn op_kind_counts(&self) -> BTreeMap<String, u64> {
+        let mut counts = BTreeMap::new();
+        for op in self.operations.values() {
+            let name = op
+                .kind
+                .and_then(|kind| self.op_kinds.get(&kind))
+                .map_or("Unknown", |kind| kind.name.as_str());
+            *counts.entry(name.to_owned()).or_insert(0) += 1;
+        }
+        counts
     }
 
-    fn record_event_node(
-        &mut self,
-        fevent: EventID,
-        kind: EventEntryKind,
-        creator: ProfUID,
-        creation_time: Timestamp,
-        trigger_time: Option<Timestamp>,
-        deduplicate: bool,
-    ) -> CriticalPathVertex {
-        if let Some(index) = self.event_lookup.get(&fevent) {
-            let node_weight = self.event_graph.node_weight_mut(*index).unwrap();
-            if node_weight.kind == EventEntryKind::UnknownEvent {
-                *node_weight =
+    pub fn task_count(&self) -> usize {
+        self.procs
+            .values()
+            .flat_map(|proc| proc.entries())
+            .filter(|entry| matches!(entry.kind, ProcEntryKind::Task(..)))
+            .count()
+    }
+
+    // Task execution time (excluding event waits) divided by the makespan
+    pub fn effective_parallelism(&self) -> f64 {
+        if self.last_time == Timestamp::ZERO {
+            return 0.0;
+        }
+        let mut total = Timestamp::ZERO;
+        for proc in self.procs.values() {
+            total += proc.meta_vs_app_time().1;
+        }
+        total.to_ns() as f64 / self.last_time.to_ns() as f64
+    }
+
+    // IO procs run overlapping tasks so their busy fraction is meaningless.
+    // Ties go to the lowest proc id. Only valid after sort_time_range.
+    pub fn busiest_proc(&self) -> Option<(ProcID, f64)> {
+        if self.last_time == Timestamp::ZERO {
+            return None;
+        }
+        let mut busiest: Option<(ProcID, f64)> = None;
+        for proc in self.procs.values() {
+            if proc.kind == Some(ProcKind::IO) {
+                continue;
+            }
+            let fraction = self.busy_fraction(proc);
+            if busiest.is_none_or(|(_, best)| fraction > best) {
+                busiest = Some((proc.proc_id, fraction));
+            }
+        }
+        busiest
+    }
+
+    // Fraction of the run the proc was busy, taking the busier of the host
+    // and device timelines on GPU procs. Only valid after sort_time_range.
+    fn busy_fraction(&self, proc: &Proc) -> f64 {
+        let busy = max(
+            proc.busy_time(Some(DeviceKind::Host)),
+            proc.busy_time(Some(DeviceKind::Device)),
+        );
+        busy.to_ns() as f64 / self.last_time.to_ns() as f64
+    }
+
+    // Procs that are busy for more than the given fraction of the run, only
+    // valid after sort_time_range
+    pub fn saturated_procs(&self, threshold: f64) -> Vec<ProcID> {
+        if self.last_time == Timestamp::ZERO {
+            return Vec::new();
+        }
+        self.procs
+            .values()
+            .filter(|proc| self.busy_fraction(proc) > threshold)
+            .map(|proc| proc.proc_id)
+            .collect()
+    }
+
+    // Application tasks running for at least the given fraction of the run
+    pub fn significant_tasks(&self, min_fraction: f64) -> Vec<ProfUID> {
+        let min_duration = min_fraction * self.last_time.to_ns() as f64;
+        self.procs
+            .values()
+            .flat_map(|proc| proc.entries.values())
+            .filter(|entry| matches!(entry.kind, ProcEntryKind::Task(..)))
+            .filter(|entry| {
+                let duration = entry.time_range.stop.unwrap() - entry.time_range.start.unwrap();
+                duration.to_ns() as f64 >= min_duration
+            })
+            .map(|entry| entry.base.prof_uid)
+            .collect()
+    }
+
+    // Uses the sorted time points when sort_time_range has already run
+    pub fn proc_first_tasks(&self) -> BTreeMap<ProcID, ProfUID> {
+        let mut result = BTreeMap::new();
+        for proc in self.procs.values() {
+            let is_task = |entry: &ProcEntry| matches!(entry.kind, ProcEntryKind::Task(..));
+            let first = proc
+                .time_points(None)
+                .iter()
+                .find(|point| is_task(proc.entry(point.entry)))
+                .map(|point| point.entry)
+                .or_else(|| {
+                    proc.entries()
+                        .filter(|entry| is_task(entry))
+                        .filter_map(|entry| Some((entry.time_range.start?, entry.base.prof_uid)))
+                        .min()
+                        .map(|(_, prof_uid)| prof_uid)
+                });
+            if let Some(prof_uid) = first {
+                result.insert(proc.proc_id, prof_uid);
+            }
+        }
+        result
+    }
+
+    // Ties go to the lowest prof_uid
+    pub fn final_task(&self) -> Option<ProfUID> {
+        self.procs
+            .values()
+            .filter(|proc| proc.is_visible())
+            .flat_map(|proc| proc.entries())
+            .filter(|entry| matches!(entry.kind, ProcEntryKind::Task(..)))
+            .filter_map(|entry| Some((entry.time_range.stop?, Reverse(entry.base.prof_uid))))
+            .max()
+            .map(|(_, Reverse(prof_uid))| prof_uid)
+    }
+
+    // Time spent running active-message handlers
+    pub fn message_variant_time(&self) -> Timestamp {
+        let mut total = Timestamp::ZERO;
+        for entry in self.procs.values().flat_map(|proc| proc.entries()) {
+            let ProcEntryKind::MetaTask(variant_id) = entry.kind else {
+                continue;
+            };
+            if !self
+                .meta_variants
+                .get(&variant_id)
+                .is_some_and(|variant| variant.message)
+            {
+                continue;
+            }
+            if let (Some(start), Some(stop)) = (entry.time_range.start, entry.time_range.stop) {
+                total += stop - start;
+            }
+        }
+        total
+    }
+
+    pub fn created_by(&self, creator: ProfUID) -> Vec<ProfUID> {
+        self.procs
+            .values()
+            .flat_map(|proc| proc.entries())
+            .filter(|entry| entry.creator == Some(creator))
+            .map(|entry| entry.base.prof_uid)
+            .collect()
+    }
+
+    pub fn instances_over(&self, bytes: u64) -> Vec<(MemID, ProfUID, u64)> {
+        let mut result = Vec::new();
+        for mem in self.mems.values() {
+            for inst in mem.insts.values() {
+                let Some(size) = inst.size else {
+                    if Config::verbose() {
+                        eprintln!(
+                            "Warning: instance {:?} in memory {:#x} has no recorded size",
+                            inst.inst_id, mem.mem_id.0
+                        );
+                    }
+                    continue;
+                };
+                if size > bytes {
+                    result.push((mem.mem_id, inst.base.prof_uid, size));
+                }
+            }
+        }
+        result.sort_by_key(|&(mem_id, prof_uid, size)| (Reverse(size), mem_id, prof_uid));
+        result
+    }
+
+    pub fn instances_with_fspace(&self, fspace_id: FSpaceID) -> Vec<ProfUID> {
+        self.mems
+            .values()
+            .flat_map(|mem| mem.insts.values())
+            .filter(|inst| inst.fspace_ids.contains(&fspace_id))
+            .map(|inst| inst.base.prof_uid)
+            .collect()
+    }
+
+    pub fn operation_depth(&self, op_id: OpID) -> u32 {
+        let mut depth = 0;
+        let mut current = op_id;
+        while let Some(parent_id) = self.find_op(current).and_then(|op| op.parent_id) {
+            // Malformed logs can have cycles in the operation tree, no real
+            // chain can be longer than the number of operations so stop there
+            if depth as usize >= self.operations.len() {
+                break;
+            }
+            depth += 1;
+            current = parent_id;
+        }
+        depth
+    }
+
+    fn find_op_mut(&mut self, op_id: OpID) -> Option<&mut Operation> {
+        self.operations.get_mut(&op_id)
+    }
+
+    fn find_op_provenance(&self, op_id: OpID) -> Option<&str> {
+        self.find_op(op_id)
+            .and_then(|op| op.provenance.and_then(|pid| self.find_provenance(pid)))
+    }
+
+    pub fn chan_entry_provenance(&self, chan_id: ChanID, prof_uid: ProfUID) -> Option<&str> {
+        self.chans
+            .get(&chan_id)?
+            .entries
+            .get(&prof_uid)?
+            .provenance(self)
+    }
+
+    fn create_fevent_reference(&mut self, fevent: EventID) -> ProfUID {
+        self.prof_uid_allocator.create_reference(fevent)
+    }
+
+    pub fn find_fevent(&self, prof_uid: ProfUID) -> EventID {
+        self.prof_uid_allocator.find_fevent(prof_uid)
+    }
+
+    // Runtime and mapper calls are not created from an fevent
+    pub fn try_find_fevent(&self, prof_uid: ProfUID) -> Option<EventID> {
+        self.prof_uid_allocator.try_find_fevent(prof_uid)
+    }
+
+    fn record_event_node(
+        &mut self,
+        fevent: EventID,
+        kind: EventEntryKind,
+        creator: ProfUID,
+        creation_time: Timestamp,
+        trigger_time: Option<Timestamp>,
+        deduplicate: bool,
+    ) -> CriticalPathVertex {
+        if let Some(index) = self.event_lookup.get(&fevent) {
+            let node_weight = self.event_graph.node_weight_mut(*index).unwrap();
+            if node_weight.kind == EventEntryKind::UnknownEvent {
+                *node_weight =
                     EventEntry::new(kind, Some(creator), Some(creation_time), trigger_time);
             } else if deduplicate {
                 assert!(node_weight.kind == kind);
@@ -3353,200 +4355,770 @@ impl State {
         }
     }
 
-    pub fn get_op_color(&self, op_id: OpID) -> Color {
-        if let Some(task) = self.find_task(op_id) {
-            match task.kind {
-                ProcEntryKind::Task(task_id, variant_id) => {
-                    return self
-                        .variants
-                        .get(&(task_id, variant_id))
-                        .unwrap()
-                        .color
-                        .unwrap();
+    pub fn cross_node_critical(&self) -> Vec<(ProfUID, ProfUID)> {
+        let mut result = Vec::new();
+        for proc in self.procs.values() {
+            for entry in proc.entries() {
+                if !matches!(entry.kind, ProcEntryKind::Task(..)) {
+                    continue;
+                }
+                let Some(critical) = entry.critical else {
+                    continue;
+                };
+                // The creator of the critical event might not have been profiled
+                let Some(creator) = self
+                    .find_critical_entry(critical)
+                    .and_then(|event_entry| event_entry.creator)
+                else {
+                    continue;
+                };
+                if let Some(creator_proc) = self.prof_uid_proc.get(&creator) {
+                    if creator_proc.node_id() != proc.proc_id.node_id() {
+                        result.push((entry.base.prof_uid, creator));
+                    }
                 }
-                _ => unreachable!(),
             }
         }
+        result
+    }
 
-        if let Some(op) = self.find_op(op_id) {
-            if let Some(kind) = op.kind {
-                return self.op_kinds.get(&kind).unwrap().color.unwrap();
+    pub fn task_duration_histogram(
+        &self,
+        task_id: Option<TaskID>,
+        bucket_ns: u64,
+    ) -> BTreeMap<u64, u64> {
+        assert!(bucket_ns > 0);
+        let mut histogram = BTreeMap::new();
+        for proc in self.procs.values() {
+            for entry in proc.entries() {
+                let ProcEntryKind::Task(entry_task_id, _) = entry.kind else {
+                    continue;
+                };
+                if task_id.is_some_and(|t| t != entry_task_id) {
+                    continue;
+                }
+                let duration = entry.time_range.stop.unwrap() - entry.time_range.start.unwrap();
+                *histogram.entry(duration.to_ns() / bucket_ns).or_insert(0) += 1;
             }
         }
+        histogram
+    }
 
-        Color::BLACK
+    pub fn task_scheduling_delays(&self) -> Vec<Timestamp> {
+        self.procs
+            .values()
+            .flat_map(|proc| proc.entries())
+            .filter(|entry| matches!(entry.kind, ProcEntryKind::Task(..)))
+            .map(|entry| {
+                let time_range = entry.time_range;
+                time_range
+                    .start
+                    .unwrap()
+                    .saturating_sub(time_range.ready.unwrap())
+            })
+            .collect()
     }
 
-    pub fn find_provenance(&self, pid: ProvenanceID) -> Option<&str> {
-        self.provenances.get(&pid).map(|p| p.name.as_str())
+    // Mean and 99th percentile (nearest rank) of the task scheduling delays
+    pub fn task_scheduling_delay_summary(&self) -> Option<(Timestamp, Timestamp)> {
+        let mut delays = self.task_scheduling_delays();
+        if delays.is_empty() {
+            return None;
+        }
+        delays.sort();
+        let total: u64 = delays.iter().map(|delay| delay.to_ns()).sum();
+        let mean = Timestamp::from_ns(total / delays.len() as u64);
+        let rank = (delays.len() * 99).div_ceil(100);
+        Some((mean, delays[rank - 1]))
     }
 
-    fn create_task(
-        &mut self,
-        op_id: OpID,
-        proc_id: ProcID,
-        task_id: TaskID,
-        variant_id: VariantID,
-        time_range: TimeRange,
-        creator: Option<EventID>,
-        critical: Option<EventID>,
-        fevent: EventID,
-        implicit: bool,
-    ) -> &mut ProcEntry {
-        // Hack: we have to do this in two places, because we don't know what
-        // order the logger calls are going to come in. If the operation gets
-        // logged first, this will come back Some(_) and we'll store it below.
-        let parent_id = self.create_op(op_id).parent_id;
-        self.tasks.insert(op_id, proc_id);
-        let alloc = &mut self.prof_uid_allocator;
-        let creator_uid = creator.map(|e| alloc.create_reference(e));
-        let base = Base::from_fevent(alloc, fevent);
-        if implicit {
-            // The fevent for implicit top-level tasks is a user event that
-            // was made by Legion and will be triggered by it so don't record
-            // that we own this event, just make sure it exists, it will be
-            // populated by the corresponding fevent
-            self.find_event_node(fevent);
-        } else {
-            // Record initially with the creation time so we can use
-            // that for determining the triggering critical path
-            assert!(time_range.stop.is_some());
-            self.record_event_node(
-                fevent,
-                EventEntryKind::TaskEvent,
-                base.prof_uid,
-                time_range.create.unwrap(),
-                time_range.stop,
-                false,
-            );
+    pub fn variant_peak_concurrency(&self, task_id: TaskID, variant_id: VariantID) -> u32 {
+        let mut points = Vec::new();
+        for proc in self.procs.values() {
+            for entry in proc.entries() {
+                if entry.kind != ProcEntryKind::Task(task_id, variant_id) {
+                    continue;
+                }
+                points.push((entry.time_range.start.unwrap(), 1));
+                points.push((entry.time_range.stop.unwrap(), -1));
+            }
         }
-        let proc = self.procs.create_proc(proc_id);
-        proc.create_proc_entry(
-            base,
-            Some(op_id),
-            parent_id,
-            ProcEntryKind::Task(task_id, variant_id),
-            time_range,
-            creator_uid,
-            critical,
-            &mut self.op_prof_uid,
-            &mut self.prof_uid_proc,
-        )
+        // Stops (-1) sort before starts (+1) at the same time so that
+        // back-to-back tasks are not counted as concurrent. A zero-length
+        // task stops before it starts and briefly takes the count down
+        // instead, which can never raise the peak.
+        points.sort_by_key(|&(time, delta)| (time, delta));
+        let mut running: i64 = 0;
+        let mut peak = 0;
+        for (_, delta) in points {
+            running += delta;
+            peak = max(peak, running);
+        }
+        peak as u32
     }
 
-    pub fn find_task(&self, op_id: OpID) -> Option<&ProcEntry> {
-        let proc = self.procs.get(self.tasks.get(&op_id)?)?;
-        proc.find_task(op_id)
+    // Only populated after stack_time_points
+    pub fn device_timelines(&self) -> Vec<(ProcID, &Vec<Vec<ProcPoint>>)> {
+        self.procs
+            .values()
+            .filter(|proc| proc.kind == Some(ProcKind::GPU))
+            .map(|proc| {
+                (
+                    proc.proc_id,
+                    proc.time_points_stacked(Some(DeviceKind::Device)),
+                )
+            })
+            .collect()
+    }
+
+    pub fn meta_spawn_latency(&self) -> Vec<(ProfUID, Timestamp)> {
+        let mut latencies = Vec::new();
+        for proc in self.procs.values() {
+            for entry in proc.entries() {
+                if !matches!(entry.kind, ProcEntryKind::MetaTask(_)) {
+                    continue;
+                }
+                // Spawn is measured on the sending node so it may be skewed
+                if let Some(spawn) = entry.time_range.spawn {
+                    let start = entry.time_range.start.unwrap();
+                    latencies.push((entry.base.prof_uid, start.saturating_sub(spawn)));
+                }
+            }
+        }
+        latencies.sort_by_key(|&(prof_uid, latency)| (Reverse(latency), prof_uid));
+        latencies
     }
 
-    fn find_task_mut(&mut self, op_id: OpID) -> Option<&mut ProcEntry> {
-        self.create_op(op_id); // FIXME: Elliott: do we REALLY need this? (and if so, yuck)
-        let proc = self.procs.get_mut(self.tasks.get(&op_id)?)?;
-        proc.find_task_mut(op_id)
+    // Procs that made no runtime calls are left out
+    pub fn runtime_overhead_by_proc(&self) -> BTreeMap<ProcID, Timestamp> {
+        self.procs
+            .values()
+            .map(|proc| (proc.proc_id, proc.runtime_call_time()))
+            .filter(|(_, time)| *time != Timestamp::ZERO)
+            .collect()
     }
 
-    fn create_meta(
-        &mut self,
-        op_id: OpID,
-        variant_id: VariantID,
-        proc_id: ProcID,
-        time_range: TimeRange,
-        creator: Option<EventID>,
-        critical: Option<EventID>,
-        fevent: EventID,
-    ) -> &mut ProcEntry {
-        self.create_op(op_id);
-        self.meta_tasks.insert((op_id, variant_id), proc_id);
-        let alloc = &mut self.prof_uid_allocator;
-        let creator_uid = creator.map(|e| alloc.create_reference(e));
-        let base = Base::from_fevent(alloc, fevent);
-        assert!(time_range.stop.is_some());
-        self.record_event_node(
-            fevent,
-            EventEntryKind::TaskEvent,
-            base.prof_uid,
-            time_range.spawn.or(time_range.create).unwrap(),
-            time_range.stop,
-            false,
-        );
-        let proc = self.procs.create_proc(proc_id);
-        proc.create_proc_entry(
-            base,
-            None,
-            Some(op_id), // FIXME: should really make this None if op_id == 0 but backwards compatibilty with Python is hard
-            ProcEntryKind::MetaTask(variant_id),
-            time_range,
-            creator_uid,
-            critical,
-            &mut self.op_prof_uid,
-            &mut self.prof_uid_proc,
-        )
+    pub fn mapper_call_totals(&self) -> BTreeMap<MapperCallKindID, (u64, Timestamp)> {
+        let mut totals = BTreeMap::new();
+        for proc in self.procs.values() {
+            for entry in proc.entries() {
+                let ProcEntryKind::MapperCall(_, _, kind) = entry.kind else {
+                    continue;
+                };
+                let duration = entry.time_range.stop.unwrap() - entry.time_range.start.unwrap();
+                let (count, total) = totals.entry(kind).or_insert((0, Timestamp::ZERO));
+                *count += 1;
+                *total += duration;
+            }
+        }
+        totals
     }
 
-    fn find_last_meta_mut(&mut self, op_id: OpID, variant_id: VariantID) -> Option<&mut ProcEntry> {
-        let proc = self
-            .procs
-            .get_mut(self.meta_tasks.get(&(op_id, variant_id))?)?;
-        proc.find_last_meta_mut(op_id, variant_id)
+    pub fn rename_task_kind(&mut self, task_id: TaskID, name: &str) {
+        self.task_kinds
+            .entry(task_id)
+            .or_insert_with(|| TaskKind::new(task_id))
+            .set_name(name, true);
     }
 
-    fn create_mapper_call(
-        &mut self,
-        mapper_id: MapperID,
-        mapper_proc: ProcID,
-        kind: MapperCallKindID,
-        proc_id: ProcID,
+    pub fn variants_of(&self, task_id: TaskID) -> Vec<VariantID> {
+        self.variants
+            .range((task_id, VariantID(0))..=(task_id, VariantID(u32::MAX)))
+            .map(|((_, variant_id), _)| *variant_id)
+            .collect()
+    }
+
+    pub fn mems_sorted(&self) -> Vec<&Mem> {
+        let mut mems: Vec<_> = self.mems.values().collect();
+        mems.sort_by_key(|mem| (mem.kind, mem.mem_id.node_id(), mem.mem_id.mem_in_node()));
+        mems
+    }
+
+    // Procs that were described but never ran anything
+    pub fn empty_procs(&self) -> Vec<ProcID> {
+        self.procs
+            .values()
+            .filter(|proc| proc.is_empty())
+            .map(|proc| proc.proc_id)
+            .collect()
+    }
+
+    pub fn unknown_events(&self) -> Vec<EventID> {
+        self.events_of_kind(EventEntryKind::UnknownEvent)
+    }
+
+    pub fn events_of_kind(&self, kind: EventEntryKind) -> Vec<EventID> {
+        self.events_matching(|other| other == kind)
+    }
+
+    // Like events_of_kind, but external events match regardless of provenance
+    pub fn events_of_kind_any_provenance(&self, kind: EventEntryKind) -> Vec<EventID> {
+        let kind = std::mem::discriminant(&kind);
+        self.events_matching(|other| std::mem::discriminant(&other) == kind)
+    }
+
+    fn events_matching(&self, matches: impl Fn(EventEntryKind) -> bool) -> Vec<EventID> {
+        self.event_lookup
+            .iter()
+            .filter(|(_, node)| {
+                let entry = self.event_graph.node_weight(**node).unwrap();
+                matches(entry.kind)
+            })
+            .map(|(event, _)| *event)
+            .collect()
+    }
+
+    fn find_entry_critical(&self, prof_uid: ProfUID) -> Option<EventID> {
+        if let Some(proc_id) = self.prof_uid_proc.get(&prof_uid) {
+            self.procs.get(proc_id)?.find_entry(prof_uid)?.critical
+        } else if let Some(chan_id) = self.prof_uid_chan.get(&prof_uid) {
+            self.chans.get(chan_id)?.find_entry(prof_uid)?.critical()
+        } else {
+            self.find_inst(prof_uid)?.critical
+        }
+    }
+
+    // Every task that the target transitively depended on, following both
+    // the edges of the event graph and the critical event of each creator
+    pub fn upstream_tasks(&self, prof_uid: ProfUID) -> Vec<ProfUID> {
+        let mut result = BTreeSet::new();
+        let Some(start) = self
+            .try_find_fevent(prof_uid)
+            .and_then(|fevent| self.event_lookup.get(&fevent))
+        else {
+            return Vec::new();
+        };
+        let mut visited = BTreeSet::from([*start]);
+        let mut queue = VecDeque::from([*start]);
+        while let Some(vertex) = queue.pop_front() {
+            let mut preds: Vec<_> = self
+                .event_graph
+                .neighbors_directed(vertex, Direction::Incoming)
+                .collect();
+            if let Some(creator) = self.event_graph[vertex].creator {
+                if creator != prof_uid && self.is_task(creator) {
+                    result.insert(creator);
+                }
+                if let Some(critical) = self
+                    .find_entry_critical(creator)
+                    .and_then(|critical| self.event_lookup.get(&critical))
+                {
+                    preds.push(*critical);
+                }
+            }
+            for pred in preds {
+                if visited.insert(pred) {
+                    queue.push_back(pred);
+                }
+            }
+        }
+        result.into_iter().collect()
+    }
+
+    fn is_task(&self, prof_uid: ProfUID) -> bool {
+        self.prof_uid_proc
+            .get(&prof_uid)
+            .and_then(|proc_id| self.procs.get(proc_id)?.find_entry(prof_uid))
+            .is_some_and(|entry| matches!(entry.kind, ProcEntryKind::Task(..)))
+    }
+
+    // Walk back along the critical path of the target through the creators
+    // of each critical event until we run out of known predecessors. Every
+    // event returned has a creator that is the next step back, except for
+    // possibly the last one.
+    pub fn critical_chain(&self, prof_uid: ProfUID) -> Vec<&EventEntry> {
+        let mut chain = Vec::new();
+        let mut visited = BTreeSet::from([prof_uid]);
+        let mut critical = self.find_entry_critical(prof_uid);
+        while let Some(event) = critical {
+            let Some(event_entry) = self.find_critical_entry(event) else {
+                break;
+            };
+            if event_entry.kind == EventEntryKind::UnknownEvent {
+                break;
+            }
+            let Some(creator) = event_entry.creator else {
+                chain.push(event_entry);
+                break;
+            };
+            if !visited.insert(creator) {
+                break;
+            }
+            chain.push(event_entry);
+            critical = self.find_entry_critical(creator);
+        }
+        chain
+    }
+
+    pub fn critical_path_length(&self, prof_uid: ProfUID) -> Option<Timestamp> {
+        let proc_id = self.prof_uid_proc.get(&prof_uid)?;
+        let entry = self.procs.get(proc_id)?.find_entry(prof_uid)?;
+        let stop = entry.time_range.stop?;
+        let earliest = self
+            .critical_chain(prof_uid)
+            .iter()
+            .filter_map(|event_entry| event_entry.creation_time)
+            .min();
+        earliest.map(|earliest| stop - min(earliest, stop))
+    }
+
+    // Event waits (entry, start, end, event) of every proc entry on the
+    // critical path of the target, oldest entry first
+    pub fn critical_path_waits(
+        &self,
+        prof_uid: ProfUID,
+    ) -> Vec<(ProfUID, Timestamp, Timestamp, EventID)> {
+        let chain = self
+            .critical_chain(prof_uid)
+            .into_iter()
+            .filter_map(|event_entry| event_entry.creator);
+        let chain: Vec<_> = std::iter::once(prof_uid).chain(chain).collect();
+        let mut waits = Vec::new();
+        for uid in chain.into_iter().rev() {
+            let Some(entry) = self
+                .prof_uid_proc
+                .get(&uid)
+                .and_then(|proc_id| self.procs.get(proc_id)?.find_entry(uid))
+            else {
+                continue;
+            };
+            for wait in &entry.waiters.wait_intervals {
+                if let (None, Some(event)) = (wait.callee, wait.event) {
+                    waits.push((uid, wait.start, wait.end, event));
+                }
+            }
+        }
+        waits
+    }
+
+    pub fn write_critical_paths_json<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        #[derive(Serialize)]
+        struct Step {
+            prof_uid: u64,
+            time: u64,
+        }
+
+        #[derive(Serialize)]
+        struct Path {
+            prof_uid: u64,
+            name: String,
+            completion: u64,
+            critical_path: Vec<Step>,
+        }
+
+        let mut paths = Vec::new();
+        for proc in self.procs.values() {
+            for entry in proc.entries() {
+                if !matches!(entry.kind, ProcEntryKind::Task(..)) {
+                    continue;
+                }
+                // Each predecessor and when the event it produced triggered
+                let steps: Vec<_> = self
+                    .critical_chain(entry.base.prof_uid)
+                    .into_iter()
+                    .map_while(|event_entry| {
+                        Some(Step {
+                            prof_uid: event_entry.creator?.0,
+                            time: event_entry.trigger_time?.to_ns(),
+                        })
+                    })
+                    .collect();
+                if steps.is_empty() {
+                    continue;
+                }
+                paths.push(Path {
+                    prof_uid: entry.base.prof_uid.0,
+                    name: entry.name(self),
+                    completion: entry.time_range.stop.unwrap().to_ns(),
+                    critical_path: steps,
+                });
+            }
+        }
+        serde_json::to_writer(w, &paths)?;
+        Ok(())
+    }
+
+    pub fn longest_event_chain(&self) -> Vec<EventID> {
+        // Ties go to the lowest event id
+        let Some((_, _, mut vertex)) = self
+            .event_lookup
+            .iter()
+            .filter_map(|(event, vertex)| {
+                let trigger_time = self.event_graph.node_weight(*vertex)?.trigger_time?;
+                Some((trigger_time, Reverse(*event), *vertex))
+            })
+            .max()
+        else {
+            return Vec::new();
+        };
+        let events: BTreeMap<_, _> = self
+            .event_lookup
+            .iter()
+            .map(|(event, vertex)| (*vertex, *event))
+            .collect();
+        // Walk back from the last event to trigger, hopping from each event
+        // to the event that determined its trigger time and from there to
+        // the critical event of the operation that created it
+        let mut chain = Vec::new();
+        let mut visited = BTreeSet::new();
+        while visited.insert(vertex) {
+            chain.push(events[&vertex]);
+            let node = self.event_graph.node_weight(vertex).unwrap();
+            if let Some(critical) = node.critical {
+                if critical != vertex {
+                    vertex = critical;
+                    continue;
+                }
+            }
+            if node.kind == EventEntryKind::UnknownEvent {
+                break;
+            }
+            let Some(critical) = node
+                .creator
+                .and_then(|creator| self.find_entry_critical(creator))
+            else {
+                break;
+            };
+            let Some(next) = self.event_lookup.get(&critical) else {
+                break;
+            };
+            vertex = *next;
+        }
+        chain.reverse();
+        chain
+    }
+
+    // Execution time of the tasks along the longest chain, per variant
+    pub fn critical_contribution_by_variant(&self) -> BTreeMap<(TaskID, VariantID), Timestamp> {
+        let mut result = BTreeMap::new();
+        for event in self.longest_event_chain() {
+            let node = self
+                .event_graph
+                .node_weight(self.event_lookup[&event])
+                .unwrap();
+            if node.kind != EventEntryKind::TaskEvent {
+                continue;
+            }
+            let Some(creator) = node.creator else {
+                continue;
+            };
+            let Some(entry) = self
+                .prof_uid_proc
+                .get(&creator)
+                .and_then(|proc_id| self.procs.get(proc_id)?.find_entry(creator))
+            else {
+                continue;
+            };
+            let ProcEntryKind::Task(task_id, variant_id) = entry.kind else {
+                continue;
+            };
+            let (Some(start), Some(stop)) = (entry.time_range.start, entry.time_range.stop) else {
+                continue;
+            };
+            *result
+                .entry((task_id, variant_id))
+                .or_insert(Timestamp::ZERO) += stop - start;
+        }
+        result
+    }
+
+    // Hide everything that is not on the longest chain, leaving only the
+    // bottleneck in the rendered profile. Must be called after both
+    // sort_time_range and compute_critical_paths. Returns false and hides
+    // nothing if there is no critical path to show.
+    pub fn mark_noncritical_hidden(&mut self) -> bool {
+        if !self.has_critical_path_data() {
+            return false;
+        }
+        let chain = self.longest_event_chain();
+        if chain.is_empty() {
+            return false;
+        }
+        let critical: BTreeSet<_> = chain
+            .into_iter()
+            .filter_map(|event| {
+                self.event_graph
+                    .node_weight(self.event_lookup[&event])?
+                    .creator
+            })
+            .collect();
+        for proc in self.procs.values_mut() {
+            for (uid, entry) in proc.entries.iter_mut() {
+                entry.base.hidden = !critical.contains(uid);
+            }
+            proc.drop_hidden_time_points();
+        }
+        for mem in self.mems.values_mut() {
+            for (uid, inst) in mem.insts.iter_mut() {
+                inst.base.hidden = !critical.contains(uid);
+            }
+            mem.drop_hidden_time_points();
+        }
+        for chan in self.chans.values_mut() {
+            for (uid, entry) in chan.entries.iter_mut() {
+                entry.base_mut().hidden = !critical.contains(uid);
+            }
+            chan.drop_hidden_time_points();
+        }
+        true
+    }
+
+    // Uses the time points from sort_time_range, so call it before
+    // stack_time_points
+    pub fn busiest_window(&self, window: Timestamp) -> (Timestamp, u64) {
+        assert!(window > Timestamp::ZERO);
+        // A task overlaps the window [s, s + window) if and only if s lies in
+        // [start - window + 1, stop), or is exactly start for a zero-length
+        // task, so find the point covered by the most of these shifted
+        // intervals with a sweep over their end points
+        let mut points = Vec::new();
+        for proc in self.procs.values() {
+            for point in proc.time_points(None) {
+                let entry = proc.entry(point.entry);
+                if !matches!(entry.kind, ProcEntryKind::Task(..)) {
+                    continue;
+                }
+                let start = point.time.to_ns();
+                let stop = max(entry.time_range.stop.unwrap().to_ns(), start + 1);
+                let first = (start + 1).saturating_sub(window.to_ns());
+                points.push((first, 1));
+                points.push((stop, -1));
+            }
+        }
+        // Process ends before starts at the same time
+        points.sort();
+        let mut active: i64 = 0;
+        let mut busiest = (Timestamp::ZERO, 0);
+        for (time, delta) in points {
+            active += delta;
+            if active as u64 > busiest.1 {
+                busiest = (Timestamp::from_ns(time), active as u64);
+            }
+        }
+        busiest
+    }
+
+    pub fn wait_backtrace(&self, prof_uid: ProfUID) -> Vec<(Timestamp, &str)> {
+        let Some(entry) = self
+            .prof_uid_proc
+            .get(&prof_uid)
+            .and_then(|proc_id| self.procs.get(proc_id)?.find_entry(prof_uid))
+        else {
+            return Vec::new();
+        };
+        entry
+            .waiters
+            .wait_intervals
+            .iter()
+            .filter_map(|wait| {
+                let backtrace = self.backtraces.get(&wait.backtrace?)?;
+                Some((wait.start, backtrace.as_str()))
+            })
+            .collect()
+    }
+
+    pub fn get_op_color(&self, op_id: OpID) -> Color {
+        if let Some(task) = self.find_task(op_id) {
+            match task.kind {
+                ProcEntryKind::Task(task_id, variant_id) => {
+                    return self
+                        .variants
+                        .get(&(task_id, variant_id))
+                        .unwrap()
+                        .color
+                        .unwrap();
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        if let Some(op) = self.find_op(op_id) {
+            if let Some(kind) = op.kind {
+                return self.op_kinds.get(&kind).unwrap().color.unwrap();
+            }
+        }
+
+        Color::BLACK
+    }
+
+    pub fn find_provenance(&self, pid: ProvenanceID) -> Option<&str> {
+        self.provenances.get(&pid).map(|p| p.name.as_str())
+    }
+
+    fn create_task(
+        &mut self,
         op_id: OpID,
+        proc_id: ProcID,
+        task_id: TaskID,
+        variant_id: VariantID,
         time_range: TimeRange,
-        fevent: Option<EventID>,
+        creator: Option<EventID>,
+        critical: Option<EventID>,
+        fevent: EventID,
+        implicit: bool,
     ) -> &mut ProcEntry {
-        self.create_op(op_id);
+        // Hack: we have to do this in two places, because we don't know what
+        // order the logger calls are going to come in. If the operation gets
+        // logged first, this will come back Some(_) and we'll store it below.
+        let parent_id = self.create_op(op_id).parent_id;
+        self.tasks.insert(op_id, proc_id);
         let alloc = &mut self.prof_uid_allocator;
-        let creator_uid = fevent.map(|e| alloc.create_reference(e));
+        let creator_uid = creator.map(|e| alloc.create_reference(e));
+        let base = Base::from_fevent(alloc, fevent);
+        if implicit {
+            // The fevent for implicit top-level tasks is a user event that
+            // was made by Legion and will be triggered by it so don't record
+            // that we own this event, just make sure it exists, it will be
+            // populated by the corresponding fevent
+            self.find_event_node(fevent);
+        } else {
+            // Record initially with the creation time so we can use
+            // that for determining the triggering critical path
+            assert!(time_range.stop.is_some());
+            self.record_event_node(
+                fevent,
+                EventEntryKind::TaskEvent,
+                base.prof_uid,
+                time_range.create.unwrap(),
+                time_range.stop,
+                false,
+            );
+        }
         let proc = self.procs.create_proc(proc_id);
         proc.create_proc_entry(
-            Base::new(alloc),
-            None,
-            if op_id != OpID::ZERO {
-                Some(op_id)
-            } else {
-                None
-            },
-            ProcEntryKind::MapperCall(mapper_id, mapper_proc, kind),
+            base,
+            Some(op_id),
+            parent_id,
+            ProcEntryKind::Task(task_id, variant_id),
             time_range,
             creator_uid,
-            None,
+            critical,
             &mut self.op_prof_uid,
             &mut self.prof_uid_proc,
         )
     }
 
-    fn create_runtime_call(
+    pub fn find_task(&self, op_id: OpID) -> Option<&ProcEntry> {
+        let proc = self.procs.get(self.tasks.get(&op_id)?)?;
+        proc.find_task(op_id)
+    }
+
+    // Includes dependence analysis and mapping as well as time spent ready
+    pub fn op_launch_latency(&self, op_id: OpID) -> Option<Timestamp> {
+        let task = self.find_task(op_id)?;
+        let create = task.time_range.create?;
+        let start = task.time_range.start?;
+        Some(start - min(create, start))
+    }
+
+    fn find_task_mut(&mut self, op_id: OpID) -> Option<&mut ProcEntry> {
+        self.create_op(op_id); // FIXME: Elliott: do we REALLY need this? (and if so, yuck)
+        let proc = self.procs.get_mut(self.tasks.get(&op_id)?)?;
+        proc.find_task_mut(op_id)
+    }
+
+    fn create_meta(
         &mut self,
-        kind: RuntimeCallKindID,
+        op_id: OpID,
+        variant_id: VariantID,
         proc_id: ProcID,
         time_range: TimeRange,
-        fevent: Option<EventID>,
+        creator: Option<EventID>,
+        critical: Option<EventID>,
+        fevent: EventID,
     ) -> &mut ProcEntry {
+        self.create_op(op_id);
+        self.meta_tasks.insert((op_id, variant_id), proc_id);
         let alloc = &mut self.prof_uid_allocator;
-        let creator_uid = fevent.map(|e| alloc.create_reference(e));
+        let creator_uid = creator.map(|e| alloc.create_reference(e));
+        let base = Base::from_fevent(alloc, fevent);
+        assert!(time_range.stop.is_some());
+        self.record_event_node(
+            fevent,
+            EventEntryKind::TaskEvent,
+            base.prof_uid,
+            time_range.spawn.or(time_range.create).unwrap(),
+            time_range.stop,
+            false,
+        );
         let proc = self.procs.create_proc(proc_id);
         proc.create_proc_entry(
-            Base::new(alloc),
-            None,
+            base,
             None,
-            ProcEntryKind::RuntimeCall(kind),
+            Some(op_id), // FIXME: should really make this None if op_id == 0 but backwards compatibilty with Python is hard
+            ProcEntryKind::MetaTask(variant_id),
             time_range,
             creator_uid,
-            None,
+            critical,
             &mut self.op_prof_uid,
             &mut self.prof_uid_proc,
         )
     }
 
-    fn create_application_call(
+    fn find_last_meta_mut(&mut self, op_id: OpID, variant_id: VariantID) -> Option<&mut ProcEntry> {
+        let proc = self
+            .procs
+            .get_mut(self.meta_tasks.get(&(op_id, variant_id))?)?;
+        proc.find_last_meta_mut(op_id, variant_id)
+    }
+
+    fn create_mapper_call(
         &mut self,
-        provenance: ProvenanceID,
+        mapper_id: MapperID,
+        mapper_proc: ProcID,
+        kind: MapperCallKindID,
+        proc_id: ProcID,
+        op_id: OpID,
+        time_range: TimeRange,
+        fevent: Option<EventID>,
+    ) -> &mut ProcEntry {
+        self.create_op(op_id);
+        let alloc = &mut self.prof_uid_allocator;
+        let creator_uid = fevent.map(|e| alloc.create_reference(e));
+        let proc = self.procs.create_proc(proc_id);
+        proc.create_proc_entry(
+            Base::new(alloc),
+            None,
+            if op_id != OpID::ZERO {
+                Some(op_id)
+            } else {
+                None
+            },
+            ProcEntryKind::MapperCall(mapper_id, mapper_proc, kind),
+            time_range,
+            creator_uid,
+            None,
+            &mut self.op_prof_uid,
+            &mut self.prof_uid_proc,
+        )
+    }
+
+    fn create_runtime_call(
+        &mut self,
+        kind: RuntimeCallKindID,
+        proc_id: ProcID,
+        time_range: TimeRange,
+        fevent: Option<EventID>,
+    ) -> &mut ProcEntry {
+        let alloc = &mut self.prof_uid_allocator;
+        let creator_uid = fevent.map(|e| alloc.create_reference(e));
+        let proc = self.procs.create_proc(proc_id);
+        proc.create_proc_entry(
+            Base::new(alloc),
+            None,
+            None,
+            ProcEntryKind::RuntimeCall(kind),
+            time_range,
+            creator_uid,
+            None,
+            &mut self.op_prof_uid,
+            &mut self.prof_uid_proc,
+        )
+    }
+
+    fn create_application_call(
+        &mut self,
+        provenance: ProvenanceID,
         proc_id: ProcID,
         time_range: TimeRange,
         fevent: Option<EventID>,
@@ -3733,6 +5305,233 @@ impl State {
         ));
     }
 
+    pub fn reservation_contention(&self) -> Vec<(ReservationID, usize)> {
+        let mut result: Vec<_> = self
+            .reservations
+            .iter()
+            .map(|(reservation, acquires)| (*reservation, acquires.len()))
+            .collect();
+        // Most contended reservations first
+        result.sort_by_key(|(reservation, count)| (Reverse(*count), *reservation));
+        result
+    }
+
+    pub fn slowest_copies(&self, n: usize) -> Vec<ProfUID> {
+        let mut copies: Vec<_> = self
+            .chans
+            .values()
+            .flat_map(|chan| chan.entries.values())
+            .filter_map(|entry| match entry {
+                ChanEntry::Copy(copy) => Some((copy.bandwidth()?, copy.base.prof_uid)),
+                _ => None,
+            })
+            .collect();
+        copies.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        copies.into_iter().take(n).map(|(_, uid)| uid).collect()
+    }
+
+    pub fn copy_queue_stats(&self) -> (Timestamp, Timestamp) {
+        let mut queue = Timestamp::ZERO;
+        let mut transfer = Timestamp::ZERO;
+        for chan in self.chans.values() {
+            for entry in chan.entries.values() {
+                if let ChanEntry::Copy(copy) = entry {
+                    queue += copy.queue_time();
+                    transfer += copy.transfer_time();
+                }
+            }
+        }
+        (queue, transfer)
+    }
+
+    // A collective id of zero means the copy is not part of a collective
+    pub fn collective_copy_groups(&self) -> BTreeMap<u32, Vec<ProfUID>> {
+        let mut groups: BTreeMap<u32, Vec<ProfUID>> = BTreeMap::new();
+        for chan in self.chans.values() {
+            for entry in chan.entries.values() {
+                if let ChanEntry::Copy(copy) = entry {
+                    if copy.collective != 0 {
+                        groups
+                            .entry(copy.collective)
+                            .or_default()
+                            .push(copy.base.prof_uid);
+                    }
+                }
+            }
+        }
+        for uids in groups.values_mut() {
+            uids.sort();
+        }
+        groups
+    }
+
+    // Instances and fields used by the op, in the order they were recorded
+    pub fn op_field_usage(&self, op_id: OpID) -> Vec<(ProfUID, FieldID)> {
+        let Some(op) = self.find_op(op_id) else {
+            return Vec::new();
+        };
+        op.operation_inst_infos
+            .iter()
+            .map(|info| (info.inst_uid, info.field_id()))
+            .collect()
+    }
+
+    // Wall-clock window during which anything for the op was running
+    pub fn op_span(&self, op_id: OpID) -> Option<(Timestamp, Timestamp)> {
+        let procs = self
+            .procs
+            .values()
+            .flat_map(|proc| proc.entries())
+            .filter(|entry| entry.op_id == Some(op_id) || entry.initiation_op == Some(op_id))
+            .map(|entry| entry.time_range);
+        let chans = self
+            .chans
+            .values()
+            .flat_map(|chan| chan.entries.values())
+            .filter(|entry| entry.initiation() == Some(op_id))
+            .map(|entry| entry.time_range());
+        procs
+            .chain(chans)
+            .filter_map(|range| Some((range.start?, range.stop?)))
+            .reduce(|(start, stop), (s, e)| (min(start, s), max(stop, e)))
+    }
+
+    pub fn op_bytes_moved(&self, op_id: OpID) -> u64 {
+        self.chans
+            .values()
+            .flat_map(|chan| chan.entries.values())
+            .filter(|entry| entry.initiation() == Some(op_id))
+            .map(|entry| match entry {
+                ChanEntry::Copy(copy) => copy.size,
+                ChanEntry::Fill(fill) => fill.size,
+                ChanEntry::DepPart(_) => 0,
+            })
+            .sum()
+    }
+
+    pub fn traffic_matrix(&self) -> BTreeMap<(MemID, MemID), u64> {
+        let mut matrix = BTreeMap::new();
+        for chan in self.chans.values() {
+            let ChanID::Copy { src, dst } = chan.chan_id else {
+                continue;
+            };
+            for entry in chan.entries.values() {
+                if let ChanEntry::Copy(copy) = entry {
+                    *matrix.entry((src, dst)).or_insert(0) += copy.size;
+                }
+            }
+        }
+        matrix
+    }
+
+    // Folded stacks in the format consumed by flamegraph.pl, one line per
+    // entry with its subcall nesting as the stack and its self time in ns
+    // as the sample count. Only meaningful after sort_time_range.
+    pub fn write_folded_stacks<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        for proc in self.procs.values() {
+            let callees: BTreeSet<_> = proc
+                .entries
+                .values()
+                .flat_map(|entry| &entry.waiters.wait_intervals)
+                .filter_map(|wait| wait.callee)
+                .collect();
+            let mut stack: Vec<(ProfUID, String)> = proc
+                .entries
+                .keys()
+                .rev()
+                .filter(|uid| !callees.contains(uid))
+                .map(|uid| (*uid, String::new()))
+                .collect();
+            while let Some((uid, prefix)) = stack.pop() {
+                let entry = proc.entry(uid);
+                // Semicolons separate frames so they can't appear in names
+                let name = entry.name(self).replace(';', ",");
+                let frames = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{};{}", prefix, name)
+                };
+                writeln!(w, "{} {}", frames, entry.self_time().to_ns())?;
+                for callee in proc.subcalls_of(uid).into_iter().rev() {
+                    stack.push((callee, frames.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Bytes written by fills into each destination memory
+    pub fn fill_targets(&self) -> BTreeMap<MemID, u64> {
+        let mut targets = BTreeMap::new();
+        for chan in self.chans.values() {
+            let ChanID::Fill { dst } = chan.chan_id else {
+                continue;
+            };
+            for entry in chan.entries.values() {
+                if let ChanEntry::Fill(fill) = entry {
+                    *targets.entry(dst).or_insert(0) += fill.size;
+                }
+            }
+        }
+        targets
+    }
+
+    // Bytes moved by gather and scatter copies, which have no single memory pair
+    pub fn indirect_traffic(&self) -> u64 {
+        self.chans
+            .values()
+            .filter(|chan| matches!(chan.chan_id, ChanID::Gather { .. } | ChanID::Scatter { .. }))
+            .flat_map(|chan| chan.entries.values())
+            .map(|entry| match entry {
+                ChanEntry::Copy(copy) => copy.size,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    pub fn deppart_counts(&self) -> BTreeMap<DepPartKind, u64> {
+        let mut counts = BTreeMap::new();
+        for chan in self.chans.values() {
+            if !matches!(chan.chan_id, ChanID::DepPart { .. }) {
+                continue;
+            }
+            for entry in chan.entries.values() {
+                if let ChanEntry::DepPart(deppart) = entry {
+                    *counts.entry(deppart.part_op).or_insert(0) += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    pub fn deppart_total_time(&self) -> Timestamp {
+        let mut total = Timestamp::ZERO;
+        for chan in self.chans.values() {
+            if !matches!(chan.chan_id, ChanID::DepPart { .. }) {
+                continue;
+            }
+            for entry in chan.entries.values() {
+                if let ChanEntry::DepPart(deppart) = entry {
+                    total += deppart.time_range.stop.unwrap() - deppart.time_range.start.unwrap();
+                }
+            }
+        }
+        total
+    }
+
+    pub fn channels_for_memory(&self, mem_id: MemID) -> Vec<ChanID> {
+        self.chans
+            .keys()
+            .filter(|chan_id| match **chan_id {
+                ChanID::Copy { src, dst } => src == mem_id || dst == mem_id,
+                ChanID::Fill { dst } | ChanID::Gather { dst } => dst == mem_id,
+                ChanID::Scatter { src } => src == mem_id,
+                ChanID::DepPart { .. } => false,
+            })
+            .copied()
+            .collect()
+    }
+
     fn find_chan_mut(&mut self, chan_id: ChanID) -> &mut Chan {
         self.chans
             .entry(chan_id)
@@ -3756,6 +5555,34 @@ impl State {
         mem.insts.get(&inst_uid)
     }
 
+    // Oldest instance first, ending with inst_uid itself
+    pub fn instance_redistrict_chain(&self, inst_uid: ProfUID) -> Vec<ProfUID> {
+        let mut chain = vec![inst_uid];
+        let mut visited = BTreeSet::from([inst_uid]);
+        let mut current = inst_uid;
+        while let Some(previous) = self.find_inst(current).and_then(|inst| inst.previous) {
+            if !visited.insert(previous) {
+                break;
+            }
+            chain.push(previous);
+            current = previous;
+        }
+        chain.reverse();
+        chain
+    }
+
+    pub fn name_of(&self, prof_uid: ProfUID) -> Option<String> {
+        if let Some(proc_id) = self.prof_uid_proc.get(&prof_uid) {
+            let entry = self.procs.get(proc_id)?.find_entry(prof_uid)?;
+            Some(entry.name(self))
+        } else if let Some(chan_id) = self.prof_uid_chan.get(&prof_uid) {
+            let entry = self.chans.get(chan_id)?.find_entry(prof_uid)?;
+            Some(entry.name(self))
+        } else {
+            self.find_inst(prof_uid).map(|inst| inst.name(self))
+        }
+    }
+
     fn find_index_space_mut(&mut self, ispace_id: ISpaceID) -> &mut ISpace {
         self.index_spaces
             .entry(ispace_id)
@@ -3778,7 +5605,488 @@ impl State {
         self.last_time = max(value, self.last_time);
     }
 
-    pub fn process_records(&mut self, records: &Vec<Record>, call_threshold: Timestamp) {
+    // Convenience for building a fully parsed state from a single set of records
+    pub fn from_records<I: IntoIterator<Item = Record>>(
+        records: I,
+        call_threshold: Timestamp,
+    ) -> Result<State, ParseError> {
+        let mut state = State::default();
+        state.process_record_stream(records.into_iter(), call_threshold)?;
+        state.complete_parse();
+        Ok(state)
+    }
+
+    // Rebuild the timeline-bearing records from the parsed state so that an
+    // edited or trimmed profile can be written back out. Event graph records
+    // are not reconstructed, so critical events are dropped, and entries
+    // whose completion event was lost (e.g. split copies) get fresh events.
+    pub fn to_records(&self) -> Vec<Record> {
+        let mut records = Vec::new();
+        // Fresh events must not collide with any event we might emit
+        let proc_events = self.procs.values().flat_map(|proc| {
+            proc.entries.values().flat_map(|entry| {
+                let waits = entry.waiters.wait_intervals.iter().filter_map(|w| w.event);
+                entry.critical.into_iter().chain(waits)
+            })
+        });
+        let chan_events = self
+            .chans
+            .values()
+            .flat_map(|chan| chan.entries.values().filter_map(|entry| entry.critical()));
+        let mut next_event = self
+            .prof_uid_allocator
+            .reverse_lookup
+            .values()
+            .chain(self.event_lookup.keys())
+            .copied()
+            .chain(proc_events)
+            .chain(chan_events)
+            .map(|e| e.0.get())
+            .max()
+            .unwrap_or(0);
+        let mut fresh_event = || {
+            next_event += 1;
+            EventID(NonZeroU64::new(next_event).unwrap())
+        };
+        let fevent_of = |uid: ProfUID| self.try_find_fevent(uid);
+        // Event waits of an entry, including those moved into its subcalls
+        fn event_waits(
+            proc: &Proc,
+            entry: &ProcEntry,
+            waits: &mut Vec<(Timestamp, Timestamp, Timestamp, EventID)>,
+        ) {
+            for wait in &entry.waiters.wait_intervals {
+                if let Some(callee) = wait.callee {
+                    if let Some(call) = proc.entries.get(&callee) {
+                        event_waits(proc, call, waits);
+                    }
+                } else if let Some(event) = wait.event {
+                    waits.push((wait.start, wait.ready, wait.end, event));
+                }
+            }
+        }
+
+        for (task_id, kind) in &self.task_kinds {
+            if let Some(name) = &kind.name {
+                records.push(Record::TaskKind {
+                    task_id: *task_id,
+                    name: name.clone(),
+                    overwrite: false,
+                });
+            }
+        }
+        for ((task_id, variant_id), variant) in &self.variants {
+            records.push(Record::TaskVariant {
+                task_id: *task_id,
+                variant_id: *variant_id,
+                name: variant.name.clone(),
+            });
+        }
+        for (kind, variant) in &self.meta_variants {
+            records.push(Record::MetaDesc {
+                kind: *kind,
+                message: variant.message,
+                ordered_vc: variant._ordered_vc,
+                name: variant.name.clone(),
+            });
+        }
+        for (kind, op_kind) in &self.op_kinds {
+            records.push(Record::OpDesc {
+                kind: kind.0,
+                name: op_kind.name.clone(),
+            });
+        }
+        for (kind, call_kind) in &self.mapper_call_kinds {
+            records.push(Record::MapperCallDesc {
+                kind: *kind,
+                name: call_kind.name.clone(),
+            });
+        }
+        for (kind, call_kind) in &self.runtime_call_kinds {
+            records.push(Record::RuntimeCallDesc {
+                kind: *kind,
+                name: call_kind.name.clone(),
+            });
+        }
+        for ((mapper_id, mapper_proc), mapper) in &self.mappers {
+            records.push(Record::MapperName {
+                mapper_id: *mapper_id,
+                mapper_proc: *mapper_proc,
+                name: mapper.name.clone(),
+            });
+        }
+        for (pid, provenance) in &self.provenances {
+            records.push(Record::Provenance {
+                pid: *pid,
+                provenance: provenance.name.clone(),
+            });
+        }
+        for (op_id, op) in &self.operations {
+            if let Some(kind) = op.kind {
+                records.push(Record::OperationInstance {
+                    op_id: *op_id,
+                    parent_id: op.parent_id,
+                    kind: kind.0,
+                    provenance: op.provenance,
+                });
+            }
+        }
+        for (mem_id, mem) in &self.mems {
+            records.push(Record::MemDesc {
+                mem_id: *mem_id,
+                kind: mem.kind as i32,
+                capacity: mem.capacity,
+            });
+        }
+
+        let allocations: BTreeMap<(Timestamp, Timestamp), ProfUID> = self
+            .mems
+            .values()
+            .flat_map(|mem| mem.insts.iter())
+            .filter_map(|(uid, inst)| {
+                Some(((inst.time_range.spawn?, inst.time_range.ready?), *uid))
+            })
+            .collect();
+        for (proc_id, proc) in &self.procs {
+            if let Some(kind) = proc.kind {
+                records.push(Record::ProcDesc {
+                    proc_id: *proc_id,
+                    kind: kind as i32,
+                    cuda_device_uuid: Uuid(Vec::new()),
+                });
+            }
+            // GPU tasks are recorded once but show up as two entries
+            let gpu_ranges: BTreeMap<OpID, (Timestamp, Timestamp)> = proc
+                .entries
+                .values()
+                .filter(|entry| matches!(entry.kind, ProcEntryKind::GPUKernel(..)))
+                .filter_map(|entry| {
+                    Some((
+                        entry.op_id?,
+                        (entry.time_range.start?, entry.time_range.stop?),
+                    ))
+                })
+                .collect();
+            // Once sort_time_range has run, calls are nested inside each other
+            // and own the waits that happened during them. Their records refer
+            // to the task that made them and the waits go back to that task.
+            let is_call = |entry: &ProcEntry| {
+                matches!(
+                    entry.kind,
+                    ProcEntryKind::MapperCall(..)
+                        | ProcEntryKind::RuntimeCall(_)
+                        | ProcEntryKind::ApplicationCall(_)
+                )
+            };
+            let owner_fevent = |entry: &ProcEntry| {
+                let mut creator = entry.creator;
+                while let Some(call) = creator
+                    .and_then(|uid| proc.entries.get(&uid))
+                    .filter(|call| is_call(call))
+                {
+                    creator = call.creator;
+                }
+                creator.and_then(fevent_of)
+            };
+            for entry in proc.entries.values() {
+                let (Some(start), Some(stop)) = (entry.time_range.start, entry.time_range.stop)
+                else {
+                    continue;
+                };
+                match entry.kind {
+                    ProcEntryKind::MapperCall(mapper_id, mapper_proc, kind) => {
+                        // Nested calls take on the op of their task
+                        records.push(Record::MapperCallInfo {
+                            mapper_id,
+                            mapper_proc,
+                            kind,
+                            op_id: entry.initiation_op.unwrap_or(OpID::ZERO),
+                            start,
+                            stop,
+                            proc_id: *proc_id,
+                            fevent: owner_fevent(entry),
+                        });
+                    }
+                    ProcEntryKind::RuntimeCall(kind) => {
+                        records.push(Record::RuntimeCallInfo {
+                            kind,
+                            start,
+                            stop,
+                            proc_id: *proc_id,
+                            fevent: owner_fevent(entry),
+                        });
+                    }
+                    ProcEntryKind::ApplicationCall(provenance) => {
+                        records.push(Record::ApplicationCallInfo {
+                            provenance,
+                            start,
+                            stop,
+                            proc_id: *proc_id,
+                            fevent: owner_fevent(entry),
+                        });
+                    }
+                    ProcEntryKind::ProfTask => {
+                        // Parsing swaps the creator of a profiling task for the
+                        // creator of the thing it profiled, so find the original
+                        // one again: completion responses keep it as their
+                        // critical event, and allocation responses match the
+                        // instance they recorded the allocation time for
+                        let creator = entry.critical.or_else(|| {
+                            allocations
+                                .get(&(start, entry.time_range.ready?))
+                                .and_then(|uid| fevent_of(*uid))
+                        });
+                        let (Some(fevent), Some(creator)) =
+                            (fevent_of(entry.base.prof_uid), creator)
+                        else {
+                            continue;
+                        };
+                        records.push(Record::ProfTaskInfo {
+                            proc_id: *proc_id,
+                            op_id: entry.initiation_op.unwrap_or(OpID::ZERO),
+                            start,
+                            stop,
+                            creator,
+                            fevent,
+                            completion: entry.critical.is_some(),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            for entry in proc.entries.values() {
+                let Some(fevent) = fevent_of(entry.base.prof_uid) else {
+                    continue;
+                };
+                let Some(op_id) = entry.op_id else {
+                    continue;
+                };
+                let range = &entry.time_range;
+                let (Some(create), Some(ready), Some(start), Some(stop)) =
+                    (range.create, range.ready, range.start, range.stop)
+                else {
+                    continue;
+                };
+                let creator = entry.creator.and_then(fevent_of);
+                let mut waits = Vec::new();
+                event_waits(proc, entry, &mut waits);
+                match entry.kind {
+                    ProcEntryKind::Task(task_id, variant_id) => {
+                        if let Some(&(gpu_start, gpu_stop)) = gpu_ranges.get(&op_id) {
+                            records.push(Record::GPUTaskInfo {
+                                op_id,
+                                task_id,
+                                variant_id,
+                                proc_id: *proc_id,
+                                create,
+                                ready,
+                                start,
+                                stop,
+                                gpu_start,
+                                gpu_stop,
+                                creator,
+                                critical: None,
+                                fevent,
+                            });
+                        } else {
+                            records.push(Record::TaskInfo {
+                                op_id,
+                                task_id,
+                                variant_id,
+                                proc_id: *proc_id,
+                                create,
+                                ready,
+                                start,
+                                stop,
+                                creator,
+                                critical: None,
+                                fevent,
+                            });
+                        }
+                        for (wait_start, wait_ready, wait_end, wait_event) in waits {
+                            records.push(Record::TaskWaitInfo {
+                                op_id,
+                                task_id,
+                                variant_id,
+                                wait_start,
+                                wait_ready,
+                                wait_end,
+                                wait_event,
+                            });
+                        }
+                    }
+                    ProcEntryKind::MetaTask(lg_id) => {
+                        if let Some(spawn) = range.spawn {
+                            records.push(Record::MessageInfo {
+                                op_id,
+                                lg_id,
+                                proc_id: *proc_id,
+                                spawn,
+                                create,
+                                ready,
+                                start,
+                                stop,
+                                creator,
+                                critical: None,
+                                fevent,
+                            });
+                        } else {
+                            records.push(Record::MetaInfo {
+                                op_id,
+                                lg_id,
+                                proc_id: *proc_id,
+                                create,
+                                ready,
+                                start,
+                                stop,
+                                creator,
+                                critical: None,
+                                fevent,
+                            });
+                        }
+                        for (wait_start, wait_ready, wait_end, wait_event) in waits {
+                            records.push(Record::MetaWaitInfo {
+                                op_id,
+                                lg_id,
+                                wait_start,
+                                wait_ready,
+                                wait_end,
+                                wait_event,
+                            });
+                        }
+                    }
+                    // Calls and profiler tasks were emitted above and GPU
+                    // kernels were emitted with their task
+                    _ => {}
+                }
+            }
+        }
+
+        for chan in self.chans.values() {
+            for entry in chan.entries.values() {
+                match entry {
+                    ChanEntry::Copy(copy) => {
+                        let range = &copy.time_range;
+                        let (Some(create), Some(ready), Some(start), Some(stop)) =
+                            (range.create, range.ready, range.start, range.stop)
+                        else {
+                            continue;
+                        };
+                        let fevent = fevent_of(copy.base.prof_uid).unwrap_or_else(&mut fresh_event);
+                        records.push(Record::CopyInfo {
+                            op_id: copy.op_id,
+                            size: copy.size,
+                            create,
+                            ready,
+                            start,
+                            stop,
+                            creator: copy.creator.and_then(fevent_of),
+                            critical: None,
+                            fevent,
+                            collective: copy.collective,
+                        });
+                        for info in &copy.copy_inst_infos {
+                            records.push(Record::CopyInstInfo {
+                                src: info.src.unwrap_or(MemID(0)),
+                                dst: info.dst.unwrap_or(MemID(0)),
+                                src_fid: info.src_fid,
+                                dst_fid: info.dst_fid,
+                                src_inst: info.src_inst_uid.and_then(fevent_of),
+                                dst_inst: info.dst_inst_uid.and_then(fevent_of),
+                                fevent,
+                                num_hops: info.num_hops,
+                                indirect: info.indirect,
+                            });
+                        }
+                    }
+                    ChanEntry::Fill(fill) => {
+                        let range = &fill.time_range;
+                        let (Some(create), Some(ready), Some(start), Some(stop)) =
+                            (range.create, range.ready, range.start, range.stop)
+                        else {
+                            continue;
+                        };
+                        let fevent = fevent_of(fill.base.prof_uid).unwrap_or_else(&mut fresh_event);
+                        records.push(Record::FillInfo {
+                            op_id: fill.op_id,
+                            size: fill.size,
+                            create,
+                            ready,
+                            start,
+                            stop,
+                            creator: fill.creator.and_then(fevent_of),
+                            critical: None,
+                            fevent,
+                        });
+                        for info in &fill.fill_inst_infos {
+                            let Some(dst_inst) = fevent_of(info.dst_inst_uid) else {
+                                continue;
+                            };
+                            records.push(Record::FillInstInfo {
+                                dst: info._dst,
+                                fid: info.fid,
+                                dst_inst,
+                                fevent,
+                            });
+                        }
+                    }
+                    // Partition records need the machine description to
+                    // place them on a channel again
+                    ChanEntry::DepPart(_) => {}
+                }
+            }
+        }
+
+        for (mem_id, mem) in &self.mems {
+            for inst in mem.insts.values() {
+                let Some(fevent) = fevent_of(inst.base.prof_uid) else {
+                    continue;
+                };
+                let range = &inst.time_range;
+                let (Some(inst_id), Some(create), Some(ready), Some(destroy)) =
+                    (inst.inst_id, range.create, range.ready, range.stop)
+                else {
+                    continue;
+                };
+                let creator = inst
+                    .creator
+                    .and_then(fevent_of)
+                    .unwrap_or_else(&mut fresh_event);
+                records.push(Record::InstTimelineInfo {
+                    fevent,
+                    inst_id,
+                    mem_id: *mem_id,
+                    size: inst.size.unwrap_or(0),
+                    op_id: inst.op_id.unwrap_or(OpID::ZERO),
+                    create,
+                    ready,
+                    destroy,
+                    creator,
+                });
+            }
+        }
+        records
+    }
+
+    pub fn process_records(
+        &mut self,
+        records: &[Record],
+        call_threshold: Timestamp,
+    ) -> Result<(), ParseError> {
+        self.process_record_stream(records.iter(), call_threshold)
+    }
+
+    // Records can be passed by value or by reference so that callers which
+    // decode records incrementally don't need to hold the whole log in memory
+    pub fn process_record_stream<I, R>(
+        &mut self,
+        records: I,
+        call_threshold: Timestamp,
+    ) -> Result<(), ParseError>
+    where
+        I: Iterator<Item = R>,
+        R: Borrow<Record>,
+    {
         // We need a separate table here because instances can't be
         // immediately linked to their associated memory from the
         // logs. Therefore we defer this process until all records
@@ -3790,7 +6098,7 @@ impl State {
         let mut profs = BTreeMap::new();
         for record in records {
             process_record(
-                record,
+                record.borrow(),
                 self,
                 &mut node,
                 &mut insts,
@@ -3798,18 +6106,32 @@ impl State {
                 &mut fills,
                 &mut profs,
                 call_threshold,
-            );
+            )?;
         }
 
-        // put inst into memories
+        // put inst into memories, grouping them by memory first so that
+        // each memory can be filled in independently in parallel
+        let mut mem_insts: BTreeMap<MemID, Vec<Inst>> = BTreeMap::new();
         for inst in insts.into_values() {
             if let Some(mem_id) = inst.mem_id {
-                let mem = self.mems.get_mut(&mem_id).unwrap();
-                mem.add_inst(inst);
+                mem_insts.entry(mem_id).or_default().push(inst);
             } else {
                 unreachable!();
             }
         }
+        let mut placements = Vec::new();
+        for (mem_id, mem) in self.mems.iter_mut() {
+            if let Some(insts) = mem_insts.remove(mem_id) {
+                placements.push((mem, insts));
+            }
+        }
+        // Every instance should belong to a memory that we know about
+        assert!(mem_insts.is_empty());
+        placements.into_par_iter().for_each(|(mem, insts)| {
+            for inst in insts {
+                mem.add_inst(inst);
+            }
+        });
         // put fills into channels
         for mut fill in fills.into_values() {
             if !fill.fill_inst_infos.is_empty() {
@@ -3857,6 +6179,7 @@ impl State {
                 }
             }
         }
+        Ok(())
     }
 
     fn find_prof_task_times(
@@ -3918,8 +6241,18 @@ impl State {
     }
 
     pub fn trim_time_range(&mut self, start: Option<Timestamp>, stop: Option<Timestamp>) {
+        self.trim_time_range_counting(start, stop);
+    }
+
+    // Same as trim_time_range but returns the number of entries that were
+    // dropped because they fell entirely outside of the window
+    pub fn trim_time_range_counting(
+        &mut self,
+        start: Option<Timestamp>,
+        stop: Option<Timestamp>,
+    ) -> usize {
         if start.is_none() && stop.is_none() {
-            return;
+            return 0;
         }
         let start = start.unwrap_or(Timestamp::ZERO);
         let stop = stop.unwrap_or(self.last_time);
@@ -3927,83 +6260,264 @@ impl State {
         assert!(start <= stop);
         assert!(stop <= self.last_time);
 
+        let mut dropped = 0;
         for proc in self.procs.values_mut() {
-            proc.trim_time_range(start, stop);
+            dropped += proc.trim_time_range(start, stop);
         }
         for mem in self.mems.values_mut() {
-            mem.trim_time_range(start, stop);
+            dropped += mem.trim_time_range(start, stop);
         }
         for chan in self.chans.values_mut() {
-            chan.trim_time_range(start, stop);
+            dropped += chan.trim_time_range(start, stop);
         }
 
         self.last_time = stop - start;
+        dropped
     }
 
-    pub fn check_message_latencies(&self, threshold: f64 /* us */, warn_percentage: f64) {
-        assert!(threshold >= 0.0);
-        assert!((0.0..100.0).contains(&warn_percentage));
-
-        // First go through and compute the skew between the nodes
-        let mut skew_messages = 0;
-        let mut total_messages = 0;
-        let mut total_skew = Timestamp::ZERO;
-        let mut skew_nodes = BTreeMap::new();
-        let mut check_for_skew = |proc: &Proc, prof_uid: ProfUID| {
-            let entry = proc.entry(prof_uid);
-            // Check for the presence of skew
-            if entry.time_range.spawn.unwrap() <= entry.time_range.create.unwrap() {
-                return;
-            }
-            skew_messages += 1;
-            let skew = entry.time_range.spawn.unwrap() - entry.time_range.create.unwrap();
-            total_skew += skew;
-            // Find the creator processor for the creator
-            // The meta task might not have a creator if it was started by an
-            // external thread
-            if let Some(creator) = entry.creator {
-                // The creator might not have a processor if it was the start-up
-                // or endpoint meta-task which are not profiled currently or
-                // if the user didn't give us a file for the node of the creator
-                if let Some(creator_proc) = self.prof_uid_proc.get(&creator) {
-                    // Creator node should be different than execution node
-                    assert!(creator_proc.node_id() != proc.proc_id.node_id());
-                    let nodes = (creator_proc.node_id(), proc.proc_id.node_id());
-                    let node_skew = skew_nodes.entry(nodes).or_insert_with(|| (0, 0.0, 0.0));
-                    // Wellford's algorithm for online variance calculation
-                    node_skew.0 += 1;
-                    let value = skew.to_ns() as f64;
-                    let delta = value - node_skew.1;
-                    node_skew.1 += delta / node_skew.0 as f64;
-                    let delta2 = value - node_skew.1;
-                    node_skew.2 += delta * delta2;
-                }
-            }
-        };
+    pub fn node_makespan(&self, node: NodeID) -> Option<(Timestamp, Timestamp)> {
+        let mut ranges = Vec::new();
         for proc in self.procs.values() {
-            for ((_, variant_id), meta_tasks) in &proc.meta_tasks {
-                let variant = self.meta_variants.get(variant_id).unwrap();
-                if !variant.message {
-                    continue;
-                }
-                total_messages += meta_tasks.len();
-                for meta_uid in meta_tasks {
-                    check_for_skew(proc, *meta_uid);
-                }
-            }
-            // In Legion programs we should never have any skew on application tasks
-            // because they won't be launched across nodes, but for PRealm programs
-            // we can have such skew because we can spawn application tasks from one
-            // address space to another.
-            total_messages += proc.message_tasks.len();
-            for message_uid in &proc.message_tasks {
-                check_for_skew(proc, *message_uid);
+            if proc.proc_id.node_id() == node {
+                ranges.extend(proc.entries().map(|entry| entry.time_range));
             }
         }
-        if total_messages == 0 {
-            return;
+        for mem in self.mems.values() {
+            if mem.mem_id.node_id() == node {
+                ranges.extend(mem.insts.values().map(|inst| inst.time_range));
+            }
         }
-        if skew_messages != 0 {
+        for chan in self.chans.values() {
+            let on_node = match chan.chan_id {
+                ChanID::Copy { src, dst } => src.node_id() == node || dst.node_id() == node,
+                ChanID::Fill { dst } | ChanID::Gather { dst } => dst.node_id() == node,
+                ChanID::Scatter { src } => src.node_id() == node,
+                ChanID::DepPart { node_id } => node_id == node,
+            };
+            if on_node {
+                ranges.extend(chan.entries.values().map(|entry| entry.time_range()));
+            }
+        }
+        let start = ranges.iter().filter_map(|range| range.start).min()?;
+        let stop = ranges.iter().filter_map(|range| range.stop).max()?;
+        Some((start, stop))
+    }
+
+    // Nodes with at least one non-empty proc, mem or chan
+    pub fn active_nodes(&self) -> Vec<NodeID> {
+        let mut nodes = BTreeSet::new();
+        for proc in self.procs.values() {
+            if !proc.is_empty() {
+                nodes.insert(proc.proc_id.node_id());
+            }
+        }
+        for mem in self.mems.values() {
+            if !mem.is_empty() {
+                nodes.insert(mem.mem_id.node_id());
+            }
+        }
+        for chan in self.chans.values() {
+            if chan.is_empty() {
+                continue;
+            }
+            match chan.chan_id {
+                ChanID::Copy { src, dst } => {
+                    nodes.insert(src.node_id());
+                    nodes.insert(dst.node_id());
+                }
+                ChanID::Fill { dst } | ChanID::Gather { dst } => {
+                    nodes.insert(dst.node_id());
+                }
+                ChanID::Scatter { src } => {
+                    nodes.insert(src.node_id());
+                }
+                ChanID::DepPart { node_id } => {
+                    nodes.insert(node_id);
+                }
+            }
+        }
+        nodes.into_iter().collect()
+    }
+
+    pub fn first_activity(&self) -> Timestamp {
+        self.procs
+            .values()
+            .flat_map(|proc| proc.entries())
+            .filter_map(|entry| entry.time_range.start)
+            .min()
+            .unwrap_or(Timestamp::ZERO)
+    }
+
+    // Shift all times so that the first processor entry starts at zero.
+    // Like trim_time_range, this must be called before sort_time_range.
+    pub fn rebase_to_first_activity(&mut self) {
+        let offset = self.first_activity();
+        if offset == Timestamp::ZERO {
+            return;
+        }
+        for proc in self.procs.values_mut() {
+            proc.rebase(offset);
+        }
+        for mem in self.mems.values_mut() {
+            mem.rebase(offset);
+        }
+        for chan in self.chans.values_mut() {
+            chan.rebase(offset);
+        }
+        for event in self.event_graph.node_weights_mut() {
+            event.creation_time = event.creation_time.map(|t| t.saturating_sub(offset));
+            event.trigger_time = event.trigger_time.map(|t| t.saturating_sub(offset));
+        }
+        self.last_time = self.last_time.saturating_sub(offset);
+    }
+
+    // Skew statistics for the messages executed on a single processor
+    fn message_skew_stats(&self, proc: &Proc) -> MessageLatencyStats {
+        let mut stats = MessageLatencyStats::default();
+        let mut check_for_skew = |prof_uid: ProfUID| {
+            let entry = proc.entry(prof_uid);
+            // Check for the presence of skew
+            if entry.time_range.spawn.unwrap() <= entry.time_range.create.unwrap() {
+                return;
+            }
+            stats.skewed_messages += 1;
+            let skew = entry.time_range.spawn.unwrap() - entry.time_range.create.unwrap();
+            stats.total_skew += skew;
+            // Find the creator processor for the creator
+            // The meta task might not have a creator if it was started by an
+            // external thread
+            if let Some(creator) = entry.creator {
+                // The creator might not have a processor if it was the start-up
+                // or endpoint meta-task which are not profiled currently or
+                // if the user didn't give us a file for the node of the creator
+                if let Some(creator_proc) = self.prof_uid_proc.get(&creator) {
+                    // Creator node should be different than execution node
+                    assert!(creator_proc.node_id() != proc.proc_id.node_id());
+                    let nodes = (creator_proc.node_id(), proc.proc_id.node_id());
+                    stats
+                        .skew_nodes
+                        .entry(nodes)
+                        .or_default()
+                        .add(skew.to_ns() as f64);
+                }
+            }
+        };
+        for ((_, variant_id), meta_tasks) in &proc.meta_tasks {
+            let variant = self.meta_variants.get(variant_id).unwrap();
+            if !variant.message {
+                continue;
+            }
+            stats.total_messages += meta_tasks.len();
+            for meta_uid in meta_tasks {
+                check_for_skew(*meta_uid);
+            }
+        }
+        // In Legion programs we should never have any skew on application tasks
+        // because they won't be launched across nodes, but for PRealm programs
+        // we can have such skew because we can spawn application tasks from one
+        // address space to another.
+        stats.total_messages += proc.message_tasks.len();
+        for message_uid in &proc.message_tasks {
+            check_for_skew(*message_uid);
+        }
+        stats
+    }
+
+    // Latency of the messages executed on a single processor, after
+    // correcting for the skew observed between each pair of nodes
+    fn message_latencies(
+        &self,
+        proc: &Proc,
+        skew_nodes: &BTreeMap<(NodeID, NodeID), OnlineVariance>,
+    ) -> (OnlineVariance, f64, Vec<f64>) {
+        let mut latency = OnlineVariance::default();
+        let mut max_latency = 0.0f64;
+        let mut latencies = Vec::new();
+        for ((_, variant_id), meta_tasks) in &proc.meta_tasks {
+            let variant = self.meta_variants.get(variant_id).unwrap();
+            if !variant.message {
+                continue;
+            }
+            for meta_uid in meta_tasks {
+                let meta_task = proc.entry(*meta_uid);
+                // Check if there was skew to begin with
+                let spawn = meta_task.time_range.spawn.unwrap();
+                let mut create = meta_task.time_range.create.unwrap();
+                // If there was any skew shift the create time forward by the average skew amount
+                // The meta task might not have a creator if it was started by an
+                // external thread
+                if let Some(creator) = meta_task.creator {
+                    // The creator might not have a processor if it was the start-up
+                    // or endpoint meta-task which are not profiled currently or
+                    // if the user didn't give us a file for the node of the creator
+                    if let Some(creator_proc) = self.prof_uid_proc.get(&creator) {
+                        let nodes = (creator_proc.node_id(), proc.proc_id.node_id());
+                        if let Some(skew) = skew_nodes.get(&nodes) {
+                            // Just truncate fractional nanoseconds, they won't matter
+                            create += Timestamp::from_ns(skew.mean as u64);
+                        }
+                        // If we still have skew we're just going to ignore it for now
+                        // Otherwise we can check the latency of message delivery
+                        if spawn <= create {
+                            // No skew
+                            let delay = (create - spawn).to_us();
+                            latency.add(delay);
+                            max_latency = max_latency.max(delay);
+                            latencies.push(delay);
+                        }
+                    }
+                }
+            }
+        }
+        (latency, max_latency, latencies)
+    }
+
+    pub fn message_latency_stats(&self) -> MessageLatencyStats {
+        let procs: Vec<_> = self.procs.values().collect();
+
+        // First go through and compute the skew between the nodes. Each
+        // processor produces its own partial statistics which are then merged
+        // in processor order so the result is deterministic.
+        let partials: Vec<_> = procs
+            .par_iter()
+            .map(|proc| self.message_skew_stats(proc))
+            .collect();
+        let mut stats = MessageLatencyStats::default();
+        for partial in partials {
+            stats.total_messages += partial.total_messages;
+            stats.skewed_messages += partial.skewed_messages;
+            stats.total_skew += partial.total_skew;
+            for (nodes, skew) in partial.skew_nodes {
+                stats.skew_nodes.entry(nodes).or_default().merge(&skew);
+            }
+        }
+
+        // Now we can go through and look at the latency of messages while also taking
+        // into account any skew that we might have observed going the other way
+        let partials: Vec<_> = procs
+            .par_iter()
+            .map(|proc| self.message_latencies(proc, &stats.skew_nodes))
+            .collect();
+        let mut latency = OnlineVariance::default();
+        for (proc_latency, max_latency, latencies) in partials {
+            latency.merge(&proc_latency);
+            stats.max_latency_us = stats.max_latency_us.max(max_latency);
+            stats.latencies_us.extend(latencies);
+        }
+        stats.mean_latency_us = latency.mean;
+        stats.stddev_latency_us = latency.stddev();
+        stats
+    }
+
+    pub fn check_message_latencies(&self, threshold: f64 /* us */, warn_percentage: f64) {
+        assert!(threshold >= 0.0);
+        assert!((0.0..100.0).contains(&warn_percentage));
+
+        let stats = self.message_latency_stats();
+        if stats.total_messages == 0 {
+            return;
+        }
+        if stats.skewed_messages != 0 {
             println!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!! WARNING !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
             println!(
                 "Detected timing skew! Legion Prof found {} messages between nodes \
@@ -4016,22 +6530,22 @@ impl State {
                     In the meantime you can still use this profile to performance debug \
                     but you should be aware that the relative position of boxes on \
                     different nodes might not be accurate.",
-                skew_messages,
-                total_skew.to_us() / skew_messages as f64
+                stats.skewed_messages,
+                stats.total_skew.to_us() / stats.skewed_messages as f64
             );
-            for (nodes, skew) in skew_nodes.iter() {
+            for (nodes, skew) in stats.skew_nodes.iter() {
                 // Compute the average skew
                 println!(
                     "Node {} appears to be {:.3} us behind node {} for {} messages with standard deviation {:.3} us.",
                     nodes.0.0,
-                    skew.1 / 1000.0, // convert to us
+                    skew.mean / 1000.0, // convert to us
                     nodes.1.0,
-                    skew.0,
-                    (skew.2 / skew.0 as f64).sqrt() / 1000.0 // convert variance to standard deviation and then to us
+                    skew.count,
+                    skew.stddev() / 1000.0 // convert to us
                 );
                 // Skew is hopefully only going in one direction, if not warn ourselves
                 let alt = (nodes.1, nodes.0);
-                if skew_nodes.contains_key(&alt) {
+                if stats.skew_nodes.contains_key(&alt) {
                     println!(
                         "WARNING: detected bi-directional skew between nodes {} and {}",
                         nodes.0.0, nodes.1.0
@@ -4041,53 +6555,14 @@ impl State {
             println!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!! WARNING !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
         }
 
-        // Now we can go through and look for long-latency messages while also taking
-        // into account any skew that we might have observed going the other way
-
-        let mut bad_messages = 0;
-        let mut longest_latency = Timestamp::ZERO;
-
-        for proc in self.procs.values() {
-            for ((_, variant_id), meta_tasks) in &proc.meta_tasks {
-                let variant = self.meta_variants.get(variant_id).unwrap();
-                if !variant.message {
-                    continue;
-                }
-                for meta_uid in meta_tasks {
-                    let meta_task = proc.entry(*meta_uid);
-                    // Check if there was skew to begin with
-                    let spawn = meta_task.time_range.spawn.unwrap();
-                    let mut create = meta_task.time_range.create.unwrap();
-                    // If there was any skew shift the create time forward by the average skew amount
-                    // The meta task might not have a creator if it was started by an
-                    // external thread
-                    if let Some(creator) = meta_task.creator {
-                        // The creator might not have a processor if it was the start-up
-                        // or endpoint meta-task which are not profiled currently or
-                        // if the user didn't give us a file for the node of the creator
-                        if let Some(creator_proc) = self.prof_uid_proc.get(&creator) {
-                            let nodes = (creator_proc.node_id(), proc.proc_id.node_id());
-                            if let Some(skew) = skew_nodes.get(&nodes) {
-                                // Just truncate fractional nanoseconds, they won't matter
-                                create += Timestamp::from_ns(skew.1 as u64);
-                            }
-                            // If we still have skew we're just going to ignore it for now
-                            // Otherwise we can check the latency of message delivery
-                            if spawn <= create {
-                                // No skew
-                                let latency = create - spawn;
-                                if threshold <= latency.to_us() {
-                                    bad_messages += 1;
-                                }
-                                longest_latency = max(longest_latency, latency);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        // Now we can look for long-latency messages
+        let bad_messages = stats
+            .latencies_us
+            .iter()
+            .filter(|latency| threshold <= **latency)
+            .count();
 
-        let percentage = 100.0 * bad_messages as f64 / total_messages as f64;
+        let percentage = 100.0 * bad_messages as f64 / stats.total_messages as f64;
         if warn_percentage <= percentage {
             for _ in 0..5 {
                 println!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!! WARNING !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
@@ -4102,11 +6577,7 @@ impl State {
                     execute. Please report this case to the Legion developers \
                     along with an accompanying Legion Prof profile so we can \
                     better understand why the network is so congested.",
-                bad_messages,
-                threshold,
-                percentage,
-                total_messages,
-                longest_latency.to_us()
+                bad_messages, threshold, percentage, stats.total_messages, stats.max_latency_us
             );
             for _ in 0..5 {
                 println!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!! WARNING !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
@@ -4114,10 +6585,77 @@ impl State {
         }
     }
 
-    pub fn sort_time_range(&mut self) {
+    pub fn zero_length_entries(&self) -> Vec<ProfUID> {
         self.procs
-            .par_iter_mut()
-            .for_each(|(_, proc)| proc.sort_time_range());
+            .values()
+            .flat_map(|proc| proc.entries())
+            .filter(|entry| entry.time_range.start == entry.time_range.stop)
+            .map(|entry| entry.base.prof_uid)
+            .collect()
+    }
+
+    pub fn calibration_error(&self) -> i64 {
+        self.calibration_err
+    }
+
+    // Beyond this the clocks of different nodes can't be compared reliably
+    pub fn has_large_calibration_error(&self) -> bool {
+        self.calibration_err.unsigned_abs() > CALIBRATION_ERR_THRESHOLD_NS
+    }
+
+    pub fn invalid_instances(&self) -> Vec<ProfUID> {
+        self.mems
+            .values()
+            .flat_map(|mem| mem.insts.values())
+            .filter(|inst| !inst.validate_times())
+            .map(|inst| inst.base.prof_uid)
+            .collect()
+    }
+
+    pub fn coalesce_device_kernels(&mut self, max_gap: Timestamp) {
+        for proc in self.procs.values_mut() {
+            for prof_uid in proc.coalesce_device_kernels(max_gap) {
+                self.prof_uid_proc.remove(&prof_uid);
+            }
+        }
+    }
+
+    pub fn sort_time_range(&mut self) {
+        self.sort_time_range_impl(Timestamp::from_ns(Config::min_task_ns()));
+    }
+
+    fn sort_time_range_impl(&mut self, min_task_duration: Timestamp) {
+        if let Some(max_gap) = Config::coalesce_kernels() {
+            self.coalesce_device_kernels(max_gap);
+        }
+        if Config::verbose() {
+            let zero_length = self.zero_length_entries().len();
+            if zero_length > 0 {
+                eprintln!(
+                    "Warning: found {} processor entries with zero duration",
+                    zero_length
+                );
+            }
+            if self.has_large_calibration_error() {
+                eprintln!(
+                    "Warning: clock calibration error is {} ns, timings across nodes may be skewed",
+                    self.calibration_err
+                );
+            }
+            let invalid = self.invalid_instances().len();
+            if invalid > 0 {
+                eprintln!(
+                    "Warning: found {} instances that were ready before being created or destroyed before being ready",
+                    invalid
+                );
+            }
+        }
+        self.procs.par_iter_mut().for_each(|(_, proc)| {
+            proc.sort_time_range();
+            if min_task_duration > Timestamp::ZERO {
+                proc.hide_short_entries(min_task_duration);
+            }
+        });
         self.mems
             .par_iter_mut()
             .for_each(|(_, mem)| mem.sort_time_range());
@@ -4139,6 +6677,12 @@ impl State {
     }
 
     pub fn assign_colors(&mut self) {
+        self.assign_colors_impl(Config::color_by_name());
+    }
+
+    // With color_by_name, task variants take their color from a hash of
+    // their name so that colors stay stable when ids shift between builds
+    fn assign_colors_impl(&mut self, color_by_name: bool) {
         let num_colors = (self.variants.len()
             + self.meta_variants.len()
             + self.op_kinds.len()
@@ -4148,7 +6692,11 @@ impl State {
         let mut lfsr = Lfsr::new(num_colors);
         let num_colors = lfsr.max_value;
         for variant in self.variants.values_mut() {
-            variant.set_color(compute_color(lfsr.next(), num_colors));
+            if color_by_name {
+                variant.set_color(compute_name_color(&variant.name));
+            } else {
+                variant.set_color(compute_color(lfsr.next(), num_colors));
+            }
         }
         for variant in self.meta_variants.values_mut() {
             variant.set_color(match variant.variant_id.0 {
@@ -4245,10 +6793,182 @@ impl State {
         }
     }
 
+    pub fn restrict_to_op_subtree(&mut self, root: OpID) {
+        let mut children: BTreeMap<OpID, Vec<OpID>> = BTreeMap::new();
+        for (op_id, op) in &self.operations {
+            if let Some(parent_id) = op.parent_id {
+                children.entry(parent_id).or_default().push(*op_id);
+            }
+        }
+        let mut subtree = BTreeSet::new();
+        let mut stack = vec![root];
+        while let Some(op_id) = stack.pop() {
+            if subtree.insert(op_id) {
+                stack.extend(children.get(&op_id).into_iter().flatten());
+            }
+        }
+        let in_subtree = |op_id: Option<OpID>| op_id.is_some_and(|op_id| subtree.contains(&op_id));
+
+        for proc in self.procs.values_mut() {
+            if !proc
+                .entries()
+                .any(|entry| in_subtree(entry.op_id) || in_subtree(entry.initiation_op))
+            {
+                proc.visible = false;
+            }
+        }
+        for mem in self.mems.values_mut() {
+            if !mem.insts.values().any(|inst| in_subtree(inst.op_id)) {
+                mem.visible = false;
+            }
+        }
+        for chan in self.chans.values_mut() {
+            if !chan
+                .entries
+                .values()
+                .any(|entry| in_subtree(entry.initiation()))
+            {
+                chan.visible = false;
+            }
+        }
+    }
+
+    pub fn prune_event_graph_to_visible(&mut self) {
+        if self.visible_nodes.is_empty() {
+            return;
+        }
+        // An event is only pruned if we know its creator lives on a proc or
+        // chan that is no longer visible, otherwise we keep it around
+        let is_hidden = |creator: ProfUID| {
+            if let Some(proc_id) = self.prof_uid_proc.get(&creator) {
+                !self.procs.get(proc_id).is_some_and(|p| p.visible)
+            } else if let Some(chan_id) = self.prof_uid_chan.get(&creator) {
+                !self.chans.get(chan_id).is_some_and(|c| c.visible)
+            } else {
+                false
+            }
+        };
+        let hidden: BTreeSet<CriticalPathVertex> = self
+            .event_graph
+            .node_indices()
+            .filter(|v| self.event_graph[*v].creator.is_some_and(is_hidden))
+            .collect();
+        if hidden.is_empty() {
+            return;
+        }
+        // Splice out each hidden node by connecting all of its predecessors
+        // to all of its successors so that reachability between the remaining
+        // nodes is preserved
+        for &vertex in &hidden {
+            let preds: Vec<_> = self
+                .event_graph
+                .neighbors_directed(vertex, Direction::Incoming)
+                .filter(|p| *p != vertex)
+                .collect();
+            let succs: Vec<_> = self
+                .event_graph
+                .neighbors_directed(vertex, Direction::Outgoing)
+                .filter(|s| *s != vertex)
+                .collect();
+            for &pred in &preds {
+                for &succ in &succs {
+                    self.event_graph.update_edge(pred, succ, ());
+                }
+            }
+            while let Some(edge) = self.event_graph.first_edge(vertex, Direction::Incoming) {
+                self.event_graph.remove_edge(edge);
+            }
+            while let Some(edge) = self.event_graph.first_edge(vertex, Direction::Outgoing) {
+                self.event_graph.remove_edge(edge);
+            }
+        }
+        // Now rebuild the graph without the hidden nodes, removing nodes from
+        // the graph directly would invalidate the indices in the event lookup
+        let (nodes, edges) = std::mem::take(&mut self.event_graph).into_nodes_edges();
+        let mut remap = vec![None; nodes.len()];
+        for (index, node) in nodes.into_iter().enumerate() {
+            if !hidden.contains(&CriticalPathVertex::new(index)) {
+                remap[index] = Some(self.event_graph.add_node(node.weight));
+            }
+        }
+        for edge in edges {
+            if let (Some(src), Some(dst)) =
+                (remap[edge.source().index()], remap[edge.target().index()])
+            {
+                self.event_graph.add_edge(src, dst, ());
+            }
+        }
+        for node in self.event_graph.node_weights_mut() {
+            node.critical = node.critical.and_then(|c| remap[c.index()]);
+        }
+        self.event_lookup
+            .retain(|_, vertex| remap[vertex.index()].is_some());
+        for vertex in self.event_lookup.values_mut() {
+            *vertex = remap[vertex.index()].unwrap();
+        }
+    }
+
     pub fn has_critical_path_data(&self) -> bool {
         self.event_graph.edge_count() > 0
     }
 
+    pub fn find_event_cycle(&self) -> Option<Vec<EventID>> {
+        let scc = tarjan_scc(&self.event_graph)
+            .into_iter()
+            .find(|scc| scc.len() > 1 || self.event_graph.contains_edge(scc[0], scc[0]))?;
+        // Walk successors inside the component until we revisit a vertex,
+        // the suffix from that vertex is a simple cycle
+        let members: BTreeSet<_> = scc.iter().copied().collect();
+        let mut path = vec![scc[0]];
+        let start = loop {
+            let last = *path.last().unwrap();
+            let next = self
+                .event_graph
+                .neighbors_directed(last, Direction::Outgoing)
+                .find(|succ| members.contains(succ))
+                .unwrap();
+            if let Some(position) = path.iter().position(|vertex| *vertex == next) {
+                break position;
+            }
+            path.push(next);
+        };
+        let events: BTreeMap<_, _> = self
+            .event_lookup
+            .iter()
+            .map(|(event, vertex)| (*vertex, *event))
+            .collect();
+        Some(path[start..].iter().map(|vertex| events[vertex]).collect())
+    }
+
+    // After compute_critical_paths every event's critical pointer should lead
+    // to an origin that is its own critical entry, any longer walk is a loop
+    pub fn validate_critical_acyclic(&self) -> bool {
+        let max_depth = self.event_graph.node_count();
+        let mut valid = true;
+        for (event, vertex) in &self.event_lookup {
+            let mut current = *vertex;
+            let mut depth = 0;
+            while let Some(critical) = self.event_graph[current].critical {
+                if critical == current {
+                    break;
+                }
+                depth += 1;
+                if depth > max_depth {
+                    valid = false;
+                    if Config::verbose() {
+                        eprintln!(
+                            "Warning: critical path of event {:#x} does not terminate",
+                            event.0
+                        );
+                    }
+                    break;
+                }
+                current = critical;
+            }
+        }
+        valid
+    }
+
     pub fn compute_critical_paths(&mut self) {
         if !self.has_critical_path_data() {
             println!(
@@ -4363,6 +7083,9 @@ impl State {
                 eprintln!(
                     "Warning: detected a cycle in the Realm event graph. Critical paths will not be available in this profile. Please create a bug for this and attach the log files that caused it."
                 );
+                if let Some(cycle) = self.find_event_cycle() {
+                    eprintln!("Warning: events in the cycle: {:?}", cycle);
+                }
                 // clear the event lookup so we can't lookup critical paths
                 self.event_lookup.clear();
             }
@@ -4374,6 +7097,27 @@ impl State {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    BadProcKind(i32),
+    BadMemKind(i32),
+    BadDimKind(u32),
+    BadDepPartKind(i32),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::BadProcKind(kind) => write!(f, "bad processor kind {}", kind),
+            ParseError::BadMemKind(kind) => write!(f, "bad memory kind {}", kind),
+            ParseError::BadDimKind(kind) => write!(f, "bad dim kind {}", kind),
+            ParseError::BadDepPartKind(kind) => write!(f, "bad deppart kind {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 trait CreateProc {
     fn create_proc(&mut self, proc_id: ProcID) -> &mut Proc;
 }
@@ -4393,7 +7137,7 @@ fn process_record(
     fills: &mut BTreeMap<EventID, Fill>,
     profs: &mut BTreeMap<ProfUID, (EventID, ProfUID, bool)>,
     call_threshold: Timestamp,
-) {
+) -> Result<(), ParseError> {
     match record {
         Record::MapperName {
             mapper_id,
@@ -4476,13 +7220,10 @@ fn process_record(
             state.provenances.insert(*pid, Provenance::new(provenance));
         }
         Record::CalibrationErr { calibration_err } => {
-            state._calibration_err = *calibration_err;
+            state.calibration_err = *calibration_err;
         }
         Record::ProcDesc { proc_id, kind, .. } => {
-            let kind = match ProcKind::try_from(*kind) {
-                Ok(x) => x,
-                Err(_) => panic!("bad processor kind"),
-            };
+            let kind = ProcKind::try_from(*kind).map_err(|_| ParseError::BadProcKind(*kind))?;
             state.procs.create_proc(*proc_id).set_kind(kind);
         }
         Record::MemDesc {
@@ -4490,10 +7231,7 @@ fn process_record(
             kind,
             capacity,
         } => {
-            let kind = match MemKind::try_from(*kind) {
-                Ok(x) => x,
-                Err(_) => panic!("bad memory kind"),
-            };
+            let kind = MemKind::try_from(*kind).map_err(|_| ParseError::BadMemKind(*kind))?;
             state
                 .mems
                 .entry(*mem_id)
@@ -4633,10 +7371,8 @@ fn process_record(
             dim_kind,
         } => {
             let dim = Dim(*dim);
-            let dim_kind = match DimKind::try_from(*dim_kind) {
-                Ok(x) => x,
-                Err(_) => unreachable!("bad dim kind"),
-            };
+            let dim_kind =
+                DimKind::try_from(*dim_kind).map_err(|_| ParseError::BadDimKind(*dim_kind))?;
             state
                 .create_inst(*fevent, insts)
                 .add_dim_order(dim, dim_kind);
@@ -4993,10 +7729,8 @@ fn process_record(
             critical,
             fevent,
         } => {
-            let part_op = match DepPartKind::try_from(*part_op) {
-                Ok(x) => x,
-                Err(_) => panic!("bad deppart kind"),
-            };
+            let part_op = DepPartKind::try_from(*part_op)
+                .map_err(|_| ParseError::BadDepPartKind(*part_op))?;
             let time_range = TimeRange::new_full(*create, *ready, *start, *stop);
             state.create_deppart(
                 node.unwrap(),
@@ -5302,8 +8036,13 @@ fn process_record(
             fevent,
             precondition,
             performed,
-            reservation: _, // Ignoring this for now until we can do a contention analysis
+            reservation,
         } => {
+            state
+                .reservations
+                .entry(ReservationID(*reservation))
+                .or_default()
+                .push(*result);
             let creator_uid = state.create_fevent_reference(*fevent);
             let dst = state.record_event_node(
                 *result,
@@ -5408,4 +8147,3101 @@ fn process_record(
             proc.record_spawn_time(task_uid, *spawn);
         }
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::serialize::Uuid;
+
+    fn init_config() {
+        CONFIG.get_or_init(|| Config {
+            filter_input: false,
+            verbose: false,
+            all_logs: true,
+            coalesce_kernels: None,
+            exclude_prof_tasks: false,
+            color_by_name: false,
+            min_task_ns: 0,
+        });
+    }
+
+    fn event(id: u64) -> EventID {
+        EventID(NonZeroU64::new(id).unwrap())
+    }
+
+    fn op(id: u64) -> OpID {
+        OpID(NonMaxU64::new(id).unwrap())
+    }
+
+    fn proc_on(node: u64, idx: u64) -> ProcID {
+        ProcID((0x1d << 56) | (node << 40) | idx)
+    }
+
+    fn mem_on(node: u64, idx: u64) -> MemID {
+        MemID((0x1e << 56) | (node << 40) | idx)
+    }
+
+    fn ts(ns: u64) -> Timestamp {
+        Timestamp::from_ns(ns)
+    }
+
+    fn proc_desc(proc_id: ProcID, kind: ProcKind) -> Record {
+        Record::ProcDesc {
+            proc_id,
+            kind: kind as i32,
+            cuda_device_uuid: Uuid(Vec::new()),
+        }
+    }
+
+    fn task_info(op_id: u64, proc_id: ProcID, start: u64, stop: u64, fevent: u64) -> Record {
+        Record::TaskInfo {
+            op_id: op(op_id),
+            task_id: TaskID(1),
+            variant_id: VariantID(1),
+            proc_id,
+            create: ts(start),
+            ready: ts(start),
+            start: ts(start),
+            stop: ts(stop),
+            creator: None,
+            critical: None,
+            fevent: event(fevent),
+        }
+    }
+
+    fn trigger_info(result: u64, fevent: u64, precondition: u64, performed: u64) -> Record {
+        Record::EventTriggerInfo {
+            result: event(result),
+            fevent: event(fevent),
+            precondition: Some(event(precondition)),
+            performed: ts(performed),
+        }
+    }
+
+    fn message_info(
+        op_id: u64,
+        proc_id: ProcID,
+        spawn: u64,
+        create: u64,
+        stop: u64,
+        creator: u64,
+        fevent: u64,
+    ) -> Record {
+        Record::MessageInfo {
+            op_id: op(op_id),
+            lg_id: VariantID(1),
+            proc_id,
+            spawn: ts(spawn),
+            create: ts(create),
+            ready: ts(create),
+            start: ts(create),
+            stop: ts(stop),
+            creator: Some(event(creator)),
+            critical: None,
+            fevent: event(fevent),
+        }
+    }
+
+    fn copy_info(op_id: u64, start: u64, stop: u64, fevent: u64) -> Record {
+        Record::CopyInfo {
+            op_id: op(op_id),
+            size: 1024,
+            create: ts(start),
+            ready: ts(start),
+            start: ts(start),
+            stop: ts(stop),
+            creator: None,
+            critical: None,
+            fevent: event(fevent),
+            collective: 0,
+        }
+    }
+
+    fn copy_inst_info(src: MemID, dst: MemID, src_inst: u64, dst_inst: u64, fevent: u64) -> Record {
+        Record::CopyInstInfo {
+            src,
+            dst,
+            src_fid: FieldID(1),
+            dst_fid: FieldID(1),
+            src_inst: Some(event(src_inst)),
+            dst_inst: Some(event(dst_inst)),
+            fevent: event(fevent),
+            num_hops: 1,
+            indirect: false,
+        }
+    }
+
+    fn fill_info(op_id: u64, start: u64, stop: u64, fevent: u64) -> Record {
+        Record::FillInfo {
+            op_id: op(op_id),
+            size: 1024,
+            create: ts(start),
+            ready: ts(start),
+            start: ts(start),
+            stop: ts(stop),
+            creator: None,
+            critical: None,
+            fevent: event(fevent),
+        }
+    }
+
+    fn fill_inst_info(dst: MemID, dst_inst: u64, fevent: u64) -> Record {
+        Record::FillInstInfo {
+            dst,
+            fid: FieldID(1),
+            dst_inst: event(dst_inst),
+            fevent: event(fevent),
+        }
+    }
+
+    fn with_critical(mut record: Record, event_id: u64) -> Record {
+        match &mut record {
+            Record::TaskInfo { critical, .. } => *critical = Some(event(event_id)),
+            _ => unreachable!(),
+        }
+        record
+    }
+
+    fn with_creator(mut record: Record, event_id: u64) -> Record {
+        match &mut record {
+            Record::TaskInfo { creator, .. } => *creator = Some(event(event_id)),
+            _ => unreachable!(),
+        }
+        record
+    }
+
+    fn task_kind(task_id: u32, variant_id: u32, name: &str) -> Vec<Record> {
+        vec![
+            Record::TaskKind {
+                task_id: TaskID(task_id),
+                name: name.to_owned(),
+                overwrite: false,
+            },
+            Record::TaskVariant {
+                task_id: TaskID(task_id),
+                variant_id: VariantID(variant_id),
+                name: name.to_owned(),
+            },
+        ]
+    }
+
+    fn build_state(records: Vec<Record>) -> State {
+        init_config();
+        State::from_records(records, Timestamp::ZERO).unwrap()
+    }
+
+    #[test]
+    fn test_prune_event_graph_to_visible() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(1, 1));
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p1, 0, 20, 2),
+            task_info(3, p0, 30, 40, 4),
+            // Task 2 on node 1 triggers event 3 after task 1 is done
+            trigger_info(3, 2, 1, 15),
+            // Task 3 on node 0 triggers event 5 after event 3
+            trigger_info(5, 4, 3, 35),
+        ]);
+        state.visible_nodes = vec![NodeID(0)];
+        state.filter_output();
+        state.prune_event_graph_to_visible();
+
+        assert!(!state.event_lookup.contains_key(&event(2)));
+        assert!(!state.event_lookup.contains_key(&event(3)));
+        let first = state.event_lookup[&event(1)];
+        let last = state.event_lookup[&event(5)];
+        assert!(state.event_graph.find_edge(first, last).is_some());
+        assert_eq!(state.event_graph.node_count(), state.event_lookup.len());
+    }
+
+    #[test]
+    fn test_message_latency_stats() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(1, 1));
+        let state = build_state(vec![
+            Record::MetaDesc {
+                kind: VariantID(1),
+                message: true,
+                ordered_vc: false,
+                name: "Remote Message".to_owned(),
+            },
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 10_000, 1),
+            // Two messages from node 0 delivered 2us and 4us after being sent
+            message_info(1, p1, 1_000, 3_000, 4_000, 1, 2),
+            message_info(1, p1, 2_000, 6_000, 7_000, 1, 3),
+        ]);
+
+        let stats = state.message_latency_stats();
+        assert_eq!(stats.total_messages, 2);
+        assert_eq!(stats.skewed_messages, 0);
+        assert!((stats.mean_latency_us - 3.0).abs() < 1e-9);
+        assert!((stats.stddev_latency_us - 1.0).abs() < 1e-9);
+        assert!((stats.max_latency_us - 4.0).abs() < 1e-9);
+    }
+
+    fn inst_with_dims(dims: &[DimKind]) -> Inst {
+        let mut inst = Inst::new(Base::new(&mut ProfUIDAllocator::default()));
+        for (i, dim_kind) in dims.iter().enumerate() {
+            inst.add_dim_order(Dim(i as u32), *dim_kind);
+        }
+        inst
+    }
+
+    #[test]
+    fn test_layout_kind() {
+        use DimKind::*;
+        assert_eq!(
+            inst_with_dims(&[DimY, DimX, DimF]).layout_kind(),
+            LayoutKind::RowMajor
+        );
+        assert_eq!(
+            inst_with_dims(&[DimF, DimX, DimY]).layout_kind(),
+            LayoutKind::ColumnMajor
+        );
+        assert_eq!(
+            inst_with_dims(&[InnerDimX, InnerDimY, OuterDimX, OuterDimY, DimF]).layout_kind(),
+            LayoutKind::Blocked
+        );
+        assert_eq!(
+            inst_with_dims(&[DimY, DimZ, DimX, DimF]).layout_kind(),
+            LayoutKind::Unknown
+        );
+        assert_eq!(inst_with_dims(&[]).layout_kind(), LayoutKind::Unknown);
+    }
+
+    #[test]
+    fn test_timestamp_format_as() {
+        let time = ts(1_234_567_891);
+        assert_eq!(time.format_as(TimeUnit::Ns), "1234567891");
+        assert_eq!(time.format_as(TimeUnit::Us), "1234567.891");
+        assert_eq!(time.format_as(TimeUnit::Ms), "1234.567891");
+        assert_eq!(time.format_as(TimeUnit::S), "1.234567891");
+        assert_eq!(time.format_as(TimeUnit::Us), time.to_string());
+    }
+
+    #[test]
+    fn test_cross_node_critical() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(1, 1));
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            // Task 1 on node 0 triggers event 3 which task 2 on node 1 waits for
+            trigger_info(3, 1, 1, 5),
+            with_critical(task_info(2, p1, 20, 30, 2), 3),
+            // Task 3 depends on task 2 on the same node
+            with_critical(task_info(3, p1, 40, 50, 4), 2),
+        ]);
+        state.compute_critical_paths();
+
+        let task1 = state.find_task(op(1)).unwrap().base.prof_uid;
+        let task2 = state.find_task(op(2)).unwrap().base.prof_uid;
+        assert_eq!(state.cross_node_critical(), vec![(task2, task1)]);
+    }
+
+    #[test]
+    fn test_process_record_stream() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(1, 1));
+        let records = vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::GPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p1, 5, 20, 2),
+            trigger_info(3, 2, 1, 15),
+        ];
+        init_config();
+        let mut streamed = State::default();
+        streamed
+            .process_record_stream(records.clone().into_iter(), Timestamp::ZERO)
+            .unwrap();
+        streamed.complete_parse();
+        let state = build_state(records);
+
+        let entries = |state: &State| {
+            let mut result = Vec::new();
+            for proc in state.procs.values() {
+                for entry in proc.entries() {
+                    result.push((
+                        proc.proc_id,
+                        entry.base.prof_uid,
+                        entry.op_id,
+                        entry.kind,
+                        entry.time_range,
+                        entry.creator,
+                        entry.critical,
+                    ));
+                }
+            }
+            result
+        };
+        let events = |state: &State| {
+            let mut result = Vec::new();
+            for (event, node) in &state.event_lookup {
+                let entry = state.event_graph.node_weight(*node).unwrap();
+                result.push((
+                    *event,
+                    entry.creator,
+                    entry.creation_time,
+                    entry.trigger_time,
+                ));
+            }
+            result
+        };
+        assert_eq!(entries(&state).len(), 2);
+        assert_eq!(entries(&streamed), entries(&state));
+        assert_eq!(events(&streamed), events(&state));
+        assert_eq!(
+            streamed.event_graph.edge_count(),
+            state.event_graph.edge_count()
+        );
+        assert_eq!(streamed.num_nodes, state.num_nodes);
+        assert_eq!(streamed.last_time, state.last_time);
+    }
+
+    #[test]
+    fn test_channels_for_memory() {
+        let (m0, m1, m2) = (mem_on(0, 1), mem_on(0, 2), mem_on(0, 3));
+        let state = build_state(vec![
+            copy_info(1, 0, 10, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            fill_info(2, 0, 10, 2),
+            fill_inst_info(m0, 3, 2),
+            copy_info(3, 0, 10, 4),
+            copy_inst_info(m1, m2, 11, 12, 4),
+        ]);
+
+        let mut chans = state.channels_for_memory(m0);
+        chans.sort();
+        let mut expected = vec![ChanID::new_copy(m0, m1), ChanID::new_fill(m0)];
+        expected.sort();
+        assert_eq!(chans, expected);
+        assert_eq!(
+            state.channels_for_memory(m2),
+            vec![ChanID::new_copy(m1, m2)]
+        );
+    }
+
+    #[test]
+    fn test_reservation_contention() {
+        let p0 = proc_on(0, 1);
+        let acquire = |result, fevent, reservation| Record::ReservationAcquireInfo {
+            result: event(result),
+            fevent: event(fevent),
+            precondition: None,
+            performed: ts(5),
+            reservation,
+        };
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p0, 10, 20, 2),
+            acquire(3, 1, 7),
+            acquire(4, 2, 7),
+            acquire(5, 2, 9),
+        ]);
+
+        assert_eq!(
+            state.reservation_contention(),
+            vec![(ReservationID(7), 2), (ReservationID(9), 1)]
+        );
+    }
+
+    #[test]
+    fn test_task_duration_histogram() {
+        let p0 = proc_on(0, 1);
+        let mut other_task = task_info(4, p0, 100, 200, 4);
+        match &mut other_task {
+            Record::TaskInfo { task_id, .. } => *task_id = TaskID(2),
+            _ => unreachable!(),
+        }
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 5, 1),
+            task_info(2, p0, 10, 18, 2),
+            task_info(3, p0, 20, 35, 3),
+            other_task,
+        ]);
+
+        let histogram = state.task_duration_histogram(Some(TaskID(1)), 10);
+        assert_eq!(histogram, BTreeMap::from([(0, 2), (1, 1)]));
+        let histogram = state.task_duration_histogram(None, 10);
+        assert_eq!(histogram, BTreeMap::from([(0, 2), (1, 1), (10, 1)]));
+    }
+
+    #[test]
+    fn test_name_of() {
+        let (p0, m0, m1) = (proc_on(0, 1), mem_on(0, 1), mem_on(0, 2));
+        let state = build_state(vec![
+            Record::TaskKind {
+                task_id: TaskID(1),
+                name: "foo".to_owned(),
+                overwrite: false,
+            },
+            Record::TaskVariant {
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                name: "foo_cpu".to_owned(),
+            },
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            copy_info(2, 0, 10, 2),
+            copy_inst_info(m0, m1, 10, 11, 2),
+        ]);
+
+        let task = state.find_task(op(1)).unwrap().base.prof_uid;
+        assert_eq!(state.name_of(task).unwrap(), "foo [foo_cpu] <1>");
+        let (copy, _) = state
+            .prof_uid_chan
+            .iter()
+            .find(|(_, chan_id)| **chan_id == ChanID::new_copy(m0, m1))
+            .unwrap();
+        assert!(state.name_of(*copy).unwrap().contains("size=1.000 KiB"));
+        assert_eq!(state.name_of(ProfUID(12345)), None);
+    }
+
+    #[test]
+    fn test_zero_length_entries() {
+        let p0 = proc_on(0, 1);
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p0, 20, 20, 2),
+        ]);
+
+        let task = state.find_task(op(2)).unwrap().base.prof_uid;
+        assert_eq!(state.zero_length_entries(), vec![task]);
+    }
+
+    #[test]
+    fn test_mem_fragmentation() {
+        let mut mem = Mem::new(mem_on(0, 1), MemKind::System, 1000);
+        let mut allocator = ProfUIDAllocator::default();
+        // Two overlapping instances of 200 and 300 bytes followed by a third
+        // of 100 bytes allocated as the first is deleted, which counts as
+        // overlapping just like it does when stacking instances
+        for (size, ready, stop) in [(200, 0, 10), (300, 5, 20), (100, 10, 30)] {
+            let mut inst = Inst::new(Base::new(&mut allocator));
+            inst.set_size(size)
+                .set_start_stop(ts(0), ts(ready), ts(stop));
+            mem.add_inst(inst);
+        }
+        mem.sort_time_range();
+        assert!((mem.fragmentation() - 0.4).abs() < 1e-9);
+
+        // An instance that is destroyed as soon as it is ready
+        let mut short = Mem::new(mem_on(0, 3), MemKind::System, 1000);
+        let mut inst = Inst::new(Base::new(&mut allocator));
+        inst.set_size(250).set_start_stop(ts(0), ts(40), ts(40));
+        short.add_inst(inst);
+        short.sort_time_range();
+        assert!((short.fragmentation() - 0.75).abs() < 1e-9);
+
+        let empty = Mem::new(mem_on(0, 2), MemKind::System, 0);
+        assert_eq!(empty.fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn test_operation_tree() {
+        let op_instance = |op_id, parent_id: Option<u64>| Record::OperationInstance {
+            op_id: op(op_id),
+            parent_id: parent_id.map(op),
+            kind: 0,
+            provenance: None,
+        };
+        let state = build_state(vec![
+            op_instance(1, None),
+            op_instance(2, Some(1)),
+            op_instance(3, Some(1)),
+            op_instance(4, Some(3)),
+        ]);
+
+        assert_eq!(state.children_of(op(1)), vec![op(2), op(3)]);
+        assert_eq!(state.children_of(op(2)), vec![]);
+        assert_eq!(state.operation_depth(op(1)), 0);
+        assert_eq!(state.operation_depth(op(2)), 1);
+        assert_eq!(state.operation_depth(op(4)), 2);
+
+        // A cycle stops the walk instead of looping forever
+        let state = build_state(vec![op_instance(1, Some(2)), op_instance(2, Some(1))]);
+        assert_eq!(state.operation_depth(op(1)), 2);
+    }
+
+    #[test]
+    fn test_coalesce_device_kernels() {
+        let p0 = proc_on(0, 1);
+        let gpu_task = |op_id, gpu_start, gpu_stop, fevent| Record::GPUTaskInfo {
+            op_id: op(op_id),
+            task_id: TaskID(1),
+            variant_id: VariantID(1),
+            proc_id: p0,
+            create: ts(0),
+            ready: ts(0),
+            start: ts(0),
+            stop: ts(100),
+            gpu_start: ts(gpu_start),
+            gpu_stop: ts(gpu_stop),
+            creator: None,
+            critical: None,
+            fevent: event(fevent),
+        };
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::GPU),
+            gpu_task(1, 10, 20, 1),
+            gpu_task(2, 22, 30, 2),
+            gpu_task(3, 50, 60, 3),
+        ]);
+        state.coalesce_device_kernels(ts(5));
+
+        let kernels: Vec<_> = state.procs[&p0]
+            .entries()
+            .filter(|entry| matches!(entry.kind, ProcEntryKind::GPUKernel(..)))
+            .map(|entry| {
+                (
+                    entry.time_range.start.unwrap(),
+                    entry.time_range.stop.unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(kernels, vec![(ts(10), ts(30)), (ts(50), ts(60))]);
+        assert_eq!(state.prof_uid_proc.len(), 5);
+
+        // The merged kernel belongs to the first task, but the second task
+        // is still attributed to its own op on the host timeline
+        let proc = &state.procs[&p0];
+        let merged = proc
+            .entries()
+            .find(|entry| {
+                matches!(entry.kind, ProcEntryKind::GPUKernel(..))
+                    && entry.time_range.start == Some(ts(10))
+            })
+            .unwrap();
+        assert_eq!(merged.op_id, Some(op(1)));
+        assert_eq!(
+            merged.creator.map(|uid| state.find_fevent(uid)),
+            Some(event(1))
+        );
+        assert_eq!(proc.find_task(op(2)).unwrap().op_id, Some(op(2)));
+    }
+
+    #[test]
+    fn test_unknown_events() {
+        let p0 = proc_on(0, 1);
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            // Event 9 is never produced by anything in the logs
+            Record::EventMergerInfo {
+                result: event(2),
+                fevent: event(1),
+                performed: ts(5),
+                pre0: Some(event(9)),
+                pre1: None,
+                pre2: None,
+                pre3: None,
+            },
+        ]);
+
+        assert_eq!(state.unknown_events(), vec![event(9)]);
+    }
+
+    #[test]
+    fn test_chan_busy_fraction() {
+        let (m0, m1) = (mem_on(0, 1), mem_on(0, 2));
+        let mut state = build_state(vec![
+            copy_info(1, 0, 30, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            copy_info(2, 60, 80, 2),
+            copy_inst_info(m0, m1, 10, 11, 2),
+        ]);
+        state.sort_time_range();
+
+        let chan = &state.chans[&ChanID::new_copy(m0, m1)];
+        assert!((chan.busy_fraction(ts(100)) - 0.5).abs() < 1e-9);
+        let empty = Chan::new(ChanID::new_fill(m0));
+        assert_eq!(empty.busy_fraction(ts(100)), 0.0);
+    }
+
+    #[test]
+    fn test_write_chrome_trace() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(1, 2));
+        let mut records = task_kind(1, 1, "foo");
+        records.extend([
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::GPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p0, 10, 20, 2),
+            task_info(3, p1, 0, 30, 3),
+            Record::TaskWaitInfo {
+                op_id: op(3),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                wait_start: ts(5),
+                wait_ready: ts(10),
+                wait_end: ts(15),
+                wait_event: event(1),
+            },
+        ]);
+        let state = build_state(records);
+
+        let mut out = Vec::new();
+        state.write_chrome_trace(&mut out).unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let events = trace.as_array().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0]["name"], "foo <1>");
+        assert_eq!(events[0]["cat"], "CPU");
+        assert_eq!(events[0]["ph"], "X");
+        assert_eq!(events[0]["dur"], 0.01);
+        assert_eq!(events[3]["name"], "foo <3> (waiting)");
+        assert_eq!(events[3]["pid"], 1);
+        assert_eq!(events[3]["tid"], 2);
+    }
+
+    #[test]
+    fn test_critical_path_length() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            // Task 1 creates event 3 before it finishes which triggers when task 1 is done
+            trigger_info(3, 1, 1, 5),
+            with_critical(task_info(2, p0, 20, 30, 2), 3),
+            with_critical(task_info(3, p0, 40, 60, 4), 2),
+        ]);
+        state.compute_critical_paths();
+
+        let task1 = state.find_task(op(1)).unwrap().base.prof_uid;
+        let task3 = state.find_task(op(3)).unwrap().base.prof_uid;
+        assert_eq!(state.critical_path_length(task3), Some(ts(60)));
+        assert_eq!(state.critical_path_length(task1), None);
+    }
+
+    #[test]
+    fn test_parallel_inst_placement() {
+        let num_mems = 8;
+        let insts_per_mem = 1000;
+        let mut records = Vec::new();
+        for m in 0..num_mems {
+            records.push(Record::MemDesc {
+                mem_id: mem_on(0, m + 1),
+                kind: MemKind::System as i32,
+                capacity: 1 << 30,
+            });
+        }
+        let p0 = proc_on(0, 1);
+        records.push(proc_desc(p0, ProcKind::CPU));
+        records.push(task_info(1, p0, 0, 10, 1));
+        for i in 0..num_mems * insts_per_mem {
+            records.push(Record::InstTimelineInfo {
+                fevent: event(i + 2),
+                inst_id: InstID(i),
+                mem_id: mem_on(0, i % num_mems + 1),
+                size: 64,
+                op_id: op(1),
+                create: ts(i),
+                ready: ts(i),
+                destroy: ts(i + 10),
+                creator: event(1),
+            });
+        }
+        let state = build_state(records);
+
+        assert_eq!(state.mems.len(), num_mems as usize);
+        for mem in state.mems.values() {
+            assert_eq!(mem.insts.len(), insts_per_mem as usize);
+        }
+        assert_eq!(state.insts.len(), (num_mems * insts_per_mem) as usize);
+    }
+
+    #[test]
+    fn test_busiest_window() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(0, 2));
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p0, 20, 30, 2),
+            task_info(3, p1, 22, 28, 3),
+            task_info(4, p1, 35, 40, 4),
+            task_info(5, p0, 50, 60, 5),
+            task_info(6, p1, 70, 70, 6),
+        ]);
+        state.sort_time_range();
+
+        // Window [18, 23) overlaps tasks 2 and 3
+        assert_eq!(state.busiest_window(ts(5)), (ts(18), 2));
+        // Window [26, 36) overlaps tasks 2, 3, and 4
+        assert_eq!(state.busiest_window(ts(10)), (ts(26), 3));
+        assert_eq!(state.busiest_window(ts(1)), (ts(22), 2));
+
+        // A zero-length task is still inside the window that starts with it
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 5, 5, 1),
+        ]);
+        state.sort_time_range();
+        assert_eq!(state.busiest_window(ts(1)), (ts(5), 1));
+    }
+
+    #[test]
+    fn test_wait_backtrace() {
+        let p0 = proc_on(0, 1);
+        let wait = |wait_start, wait_end, wait_event| Record::TaskWaitInfo {
+            op_id: op(1),
+            task_id: TaskID(1),
+            variant_id: VariantID(1),
+            wait_start: ts(wait_start),
+            wait_ready: ts(wait_end),
+            wait_end: ts(wait_end),
+            wait_event: event(wait_event),
+        };
+        let mut state = build_state(vec![
+            Record::BacktraceDesc {
+                backtrace_id: BacktraceID(7),
+                backtrace: "foo.cc:42".to_owned(),
+            },
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            wait(10, 20, 5),
+            wait(30, 40, 6),
+            Record::EventWaitInfo {
+                proc_id: p0,
+                fevent: event(1),
+                event: event(5),
+                backtrace_id: BacktraceID(7),
+            },
+        ]);
+        state.sort_time_range();
+
+        let task = state.find_task(op(1)).unwrap().base.prof_uid;
+        assert_eq!(state.wait_backtrace(task), vec![(ts(10), "foo.cc:42")]);
+    }
+
+    #[test]
+    fn test_process_records_bad_proc_kind() {
+        init_config();
+        let mut state = State::default();
+        let records = vec![Record::ProcDesc {
+            proc_id: proc_on(0, 1),
+            kind: 42,
+            cuda_device_uuid: Uuid(Vec::new()),
+        }];
+        let result = state.process_records(&records, Timestamp::ZERO);
+        assert_eq!(result, Err(ParseError::BadProcKind(42)));
+        assert_eq!(result.unwrap_err().to_string(), "bad processor kind 42");
+    }
+
+    #[test]
+    fn test_slowest_copies() {
+        let (m0, m1, m2) = (mem_on(0, 1), mem_on(0, 2), mem_on(0, 3));
+        let state = build_state(vec![
+            copy_info(1, 0, 10, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            copy_info(2, 0, 100, 2),
+            copy_inst_info(m0, m2, 10, 12, 2),
+            // Zero duration copies have no meaningful bandwidth
+            copy_info(3, 50, 50, 3),
+            copy_inst_info(m1, m2, 11, 12, 3),
+        ]);
+
+        let copy_on = |chan_id| match state.chans[&chan_id].entries.values().next() {
+            Some(ChanEntry::Copy(copy)) => copy,
+            _ => unreachable!(),
+        };
+        let fast = copy_on(ChanID::new_copy(m0, m1));
+        let slow = copy_on(ChanID::new_copy(m0, m2));
+        assert_eq!(fast.bandwidth(), Some(102.4));
+        assert_eq!(slow.bandwidth(), Some(10.24));
+        assert_eq!(copy_on(ChanID::new_copy(m1, m2)).bandwidth(), None);
+        assert_eq!(state.slowest_copies(1), vec![slow.base.prof_uid]);
+        assert_eq!(
+            state.slowest_copies(5),
+            vec![slow.base.prof_uid, fast.base.prof_uid]
+        );
+    }
+
+    #[test]
+    fn test_deppart_counts() {
+        let partition = |op_id, part_op: DepPartKind, fevent| Record::PartitionInfo {
+            op_id: op(op_id),
+            part_op: part_op as i32,
+            create: ts(0),
+            ready: ts(0),
+            start: ts(0),
+            stop: ts(10),
+            creator: None,
+            critical: None,
+            fevent: event(fevent),
+        };
+        let state = build_state(vec![
+            Record::MachineDesc {
+                node_id: NodeID(0),
+                num_nodes: 1,
+                version: 0,
+                hostname: "localhost".to_owned(),
+                host_id: 0,
+                process_id: 0,
+            },
+            partition(1, DepPartKind::EqualPartition, 1),
+            partition(2, DepPartKind::PartitionByField, 2),
+            partition(3, DepPartKind::PartitionByField, 3),
+        ]);
+
+        assert_eq!(
+            state.deppart_counts(),
+            BTreeMap::from([
+                (DepPartKind::EqualPartition, 1),
+                (DepPartKind::PartitionByField, 2),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_rebase_to_first_activity() {
+        let (p0, m0, m1) = (proc_on(0, 1), mem_on(0, 1), mem_on(0, 2));
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 1000, 1100, 1),
+            task_info(2, p0, 1200, 1500, 2),
+            copy_info(3, 1100, 1150, 3),
+            copy_inst_info(m0, m1, 10, 11, 3),
+        ]);
+        assert_eq!(state.first_activity(), ts(1000));
+        state.rebase_to_first_activity();
+
+        assert_eq!(state.first_activity(), ts(0));
+        assert_eq!(state.last_time, ts(500));
+        let task = state.find_task(op(2)).unwrap();
+        assert_eq!(task.time_range.start, Some(ts(200)));
+        assert_eq!(task.time_range.stop, Some(ts(500)));
+        let chan = &state.chans[&ChanID::new_copy(m0, m1)];
+        let copy = chan.entries.values().next().unwrap();
+        assert_eq!(copy.time_range().start, Some(ts(100)));
+    }
+
+    #[test]
+    fn test_instances_used_by() {
+        let usage = |fevent, field_id| Record::PhysicalInstanceUsage {
+            fevent: event(fevent),
+            op_id: op(1),
+            index_id: 0,
+            field_id: FieldID(field_id),
+        };
+        let state = build_state(vec![usage(5, 1), usage(5, 2), usage(6, 1)]);
+
+        let insts: Vec<_> = state
+            .instances_used_by(op(1))
+            .into_iter()
+            .map(|inst_uid| state.prof_uid_allocator.find_fevent(inst_uid))
+            .collect();
+        assert_eq!(insts, vec![event(5), event(6)]);
+        assert_eq!(state.instances_used_by(op(2)), vec![]);
+    }
+
+    #[test]
+    fn test_node_makespan() {
+        let (p0, p1, p2) = (proc_on(0, 1), proc_on(1, 1), proc_on(1, 2));
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            proc_desc(p2, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            task_info(2, p1, 20, 50, 2),
+            task_info(3, p2, 40, 80, 3),
+        ]);
+
+        assert_eq!(state.node_makespan(NodeID(0)), Some((ts(0), ts(100))));
+        assert_eq!(state.node_makespan(NodeID(1)), Some((ts(20), ts(80))));
+        assert_eq!(state.node_makespan(NodeID(2)), None);
+    }
+
+    #[test]
+    fn test_find_event_cycle() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            trigger_info(2, 1, 3, 10),
+            trigger_info(3, 1, 2, 20),
+        ]);
+
+        let cycle = state.find_event_cycle().unwrap();
+        assert_eq!(cycle.len(), 2);
+        assert!(cycle.contains(&event(2)));
+        assert!(cycle.contains(&event(3)));
+
+        state.compute_critical_paths();
+        assert!(state.event_lookup.is_empty());
+    }
+
+    #[test]
+    fn test_find_event_cycle_acyclic() {
+        let p0 = proc_on(0, 1);
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            trigger_info(2, 1, 3, 10),
+        ]);
+
+        assert_eq!(state.find_event_cycle(), None);
+    }
+
+    #[test]
+    fn test_mapper_call_totals() {
+        let p0 = proc_on(0, 1);
+        let p1 = proc_on(0, 2);
+        let call = |proc_id, start, stop| Record::MapperCallInfo {
+            mapper_id: MapperID(0),
+            mapper_proc: proc_id,
+            kind: MapperCallKindID(3),
+            op_id: OpID::ZERO,
+            start: ts(start),
+            stop: ts(stop),
+            proc_id,
+            fevent: None,
+        };
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::Utility),
+            proc_desc(p1, ProcKind::Utility),
+            Record::MapperCallDesc {
+                kind: MapperCallKindID(3),
+                name: "map_task".to_owned(),
+            },
+            call(p0, 10, 25),
+            call(p1, 40, 45),
+        ]);
+
+        let totals = state.mapper_call_totals();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[&MapperCallKindID(3)], (2, ts(20)));
+    }
+
+    #[test]
+    fn test_mems_sorted() {
+        let mem_desc = |mem_id, kind: MemKind| Record::MemDesc {
+            mem_id,
+            kind: kind as i32,
+            capacity: 1 << 30,
+        };
+        let state = build_state(vec![
+            mem_desc(mem_on(0, 1), MemKind::Framebuffer),
+            mem_desc(mem_on(1, 2), MemKind::System),
+            mem_desc(mem_on(1, 1), MemKind::System),
+        ]);
+
+        let order: Vec<_> = state.mems_sorted().iter().map(|m| m.mem_id).collect();
+        assert_eq!(order, vec![mem_on(1, 1), mem_on(1, 2), mem_on(0, 1)]);
+    }
+
+    #[test]
+    fn test_wait_attributed_idle() {
+        let p0 = proc_on(0, 1);
+        let wait = |op_id, wait_start, wait_end| Record::TaskWaitInfo {
+            op_id: op(op_id),
+            task_id: TaskID(1),
+            variant_id: VariantID(1),
+            wait_start: ts(wait_start),
+            wait_ready: ts(wait_end),
+            wait_end: ts(wait_end),
+            wait_event: event(9),
+        };
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            wait(1, 20, 60),
+        ]);
+        state.sort_time_range();
+        assert_eq!(state.procs[&p0].wait_attributed_idle(None), ts(40));
+
+        // Another task running during part of the wait is not a stall
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            wait(1, 20, 60),
+            task_info(2, p0, 30, 50, 2),
+        ]);
+        state.sort_time_range();
+        assert_eq!(state.procs[&p0].wait_attributed_idle(None), ts(20));
+
+        // One task spanning the end of one wait and the start of the next
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            wait(1, 10, 30),
+            wait(1, 50, 80),
+            task_info(2, p0, 20, 60, 2),
+        ]);
+        state.sort_time_range();
+        assert_eq!(state.procs[&p0].wait_attributed_idle(None), ts(30));
+    }
+
+    #[test]
+    fn test_rename_task_kind() {
+        let p0 = proc_on(0, 1);
+        let mut records = task_kind(1, 1, "foo");
+        records.extend([proc_desc(p0, ProcKind::CPU), task_info(1, p0, 0, 10, 1)]);
+        let mut state = build_state(records);
+
+        let task = state.find_task(op(1)).unwrap();
+        assert_eq!(task.name(&state), "foo <1>");
+
+        state.rename_task_kind(TaskID(1), "bar");
+        let task = state.find_task(op(1)).unwrap();
+        assert_eq!(task.name(&state), "bar [foo] <1>");
+    }
+
+    #[test]
+    fn test_copy_queue_stats() {
+        let (m0, m1) = (mem_on(0, 1), mem_on(0, 2));
+        let mut queued = copy_info(1, 30, 70, 1);
+        if let Record::CopyInfo { create, ready, .. } = &mut queued {
+            *create = ts(10);
+            *ready = ts(10);
+        }
+        let state = build_state(vec![
+            queued,
+            copy_inst_info(m0, m1, 10, 11, 1),
+            copy_info(2, 100, 110, 2),
+            copy_inst_info(m1, m0, 11, 10, 2),
+        ]);
+
+        let copy = match state.chans[&ChanID::new_copy(m0, m1)]
+            .entries
+            .values()
+            .next()
+        {
+            Some(ChanEntry::Copy(copy)) => copy,
+            _ => unreachable!(),
+        };
+        assert_eq!(copy.queue_time(), ts(20));
+        assert_eq!(copy.transfer_time(), ts(40));
+        assert_eq!(state.copy_queue_stats(), (ts(20), ts(50)));
+    }
+
+    #[test]
+    fn test_copy_endpoints() {
+        let (m0, m1) = (mem_on(0, 1), mem_on(1, 2));
+        let state = build_state(vec![
+            copy_info(1, 0, 10, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+        ]);
+
+        let copy = match state.chans[&ChanID::new_copy(m0, m1)]
+            .entries
+            .values()
+            .next()
+        {
+            Some(ChanEntry::Copy(copy)) => copy,
+            _ => unreachable!(),
+        };
+        let info = &copy.copy_inst_infos[0];
+        assert_eq!(info.src_mem(), Some(m0));
+        assert_eq!(info.dst_mem(), Some(m1));
+        assert_eq!(copy.endpoints(), (Some(m0), Some(m1)));
+    }
+
+    #[test]
+    fn test_traffic_matrix() {
+        let (m0, m1) = (mem_on(0, 1), mem_on(1, 2));
+        let state = build_state(vec![
+            copy_info(1, 0, 10, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            copy_info(2, 20, 30, 2),
+            copy_inst_info(m0, m1, 10, 11, 2),
+            copy_info(3, 40, 50, 3),
+            copy_inst_info(m1, m0, 11, 10, 3),
+        ]);
+
+        let matrix = state.traffic_matrix();
+        assert_eq!(matrix.len(), 2);
+        assert_eq!(matrix[&(m0, m1)], 2048);
+        assert_eq!(matrix[&(m1, m0)], 1024);
+        assert_eq!(state.indirect_traffic(), 0);
+    }
+
+    #[test]
+    fn test_exclude_prof_tasks() {
+        let p0 = proc_on(0, 1);
+        let records = vec![
+            proc_desc(p0, ProcKind::Utility),
+            task_info(1, p0, 0, 100, 1),
+            Record::ProfTaskInfo {
+                proc_id: p0,
+                op_id: op(1),
+                start: ts(100),
+                stop: ts(140),
+                creator: event(1),
+                fevent: event(2),
+                completion: true,
+            },
+        ];
+        let util_uids = |proc: &Proc| -> BTreeSet<ProfUID> {
+            proc.util_time_points(None)
+                .iter()
+                .map(|p| p.entry)
+                .collect()
+        };
+
+        let mut state = build_state(records.clone());
+        let proc = state.procs.get_mut(&p0).unwrap();
+        proc.sort_time_range_impl(false);
+        assert_eq!(proc.time_points(None).len(), 2);
+        assert_eq!(util_uids(proc).len(), 2);
+
+        let mut state = build_state(records);
+        let proc = state.procs.get_mut(&p0).unwrap();
+        proc.sort_time_range_impl(true);
+        let task = proc.find_task(op(1)).unwrap().base.prof_uid;
+        assert_eq!(proc.time_points(None).len(), 2);
+        assert_eq!(util_uids(proc), BTreeSet::from([task]));
+    }
+
+    #[test]
+    fn test_longest_event_chain() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            trigger_info(3, 1, 1, 5),
+            // Long chain: task 1 -> event 3 -> task 2 -> task 3
+            with_critical(task_info(2, p0, 20, 30, 2), 3),
+            with_critical(task_info(3, p0, 40, 60, 4), 2),
+            // Short chain: task 1 -> task 4
+            with_critical(task_info(4, p0, 15, 25, 5), 1),
+        ]);
+        state.compute_critical_paths();
+
+        assert_eq!(
+            state.longest_event_chain(),
+            vec![event(1), event(3), event(2), event(4)]
+        );
+    }
+
+    #[test]
+    fn test_instances_with_fspace() {
+        let (p0, m0) = (proc_on(0, 1), mem_on(0, 1));
+        let inst = |fevent, inst_id| Record::InstTimelineInfo {
+            fevent: event(fevent),
+            inst_id: InstID(inst_id),
+            mem_id: m0,
+            size: 64,
+            op_id: op(1),
+            create: ts(0),
+            ready: ts(0),
+            destroy: ts(10),
+            creator: event(1),
+        };
+        let region = |fevent, fspace_id| Record::PhysicalInstRegionDesc {
+            fevent: event(fevent),
+            ispace_id: ISpaceID(1),
+            fspace_id,
+            tree_id: TreeID(1),
+        };
+        let state = build_state(vec![
+            Record::MemDesc {
+                mem_id: m0,
+                kind: MemKind::System as i32,
+                capacity: 1 << 30,
+            },
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            inst(2, 1),
+            region(2, 7),
+            inst(3, 2),
+            region(3, 8),
+        ]);
+
+        let insts: Vec<_> = state
+            .instances_with_fspace(FSpaceID(7))
+            .into_iter()
+            .map(|inst_uid| state.prof_uid_allocator.find_fevent(inst_uid))
+            .collect();
+        assert_eq!(insts, vec![event(2)]);
+        assert_eq!(state.instances_with_fspace(FSpaceID(9)), vec![]);
+    }
+
+    #[test]
+    fn test_subcalls_of() {
+        let p0 = proc_on(0, 1);
+        let call = |kind, start, stop| Record::MapperCallInfo {
+            mapper_id: MapperID(0),
+            mapper_proc: p0,
+            kind: MapperCallKindID(kind),
+            op_id: op(1),
+            start: ts(start),
+            stop: ts(stop),
+            proc_id: p0,
+            fevent: Some(event(1)),
+        };
+        let desc = |kind, name: &str| Record::MapperCallDesc {
+            kind: MapperCallKindID(kind),
+            name: name.to_owned(),
+        };
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            desc(1, "map_task"),
+            desc(2, "select_task_options"),
+            task_info(1, p0, 0, 100, 1),
+            call(1, 40, 50),
+            call(2, 10, 20),
+        ]);
+        state.sort_time_range();
+
+        let proc = &state.procs[&p0];
+        let task = proc.find_task(op(1)).unwrap().base.prof_uid;
+        let kinds: Vec<_> = proc
+            .subcalls_of(task)
+            .into_iter()
+            .map(|uid| proc.find_entry(uid).unwrap().kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ProcEntryKind::MapperCall(MapperID(0), p0, MapperCallKindID(2)),
+                ProcEntryKind::MapperCall(MapperID(0), p0, MapperCallKindID(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variant_peak_concurrency() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(0, 2));
+        let zero_length = |op_id, variant, time, fevent| {
+            let mut record = task_info(op_id, p1, time, time, fevent);
+            if let Record::TaskInfo { variant_id, .. } = &mut record {
+                *variant_id = VariantID(variant);
+            }
+            record
+        };
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 50, 1),
+            task_info(2, p1, 20, 70, 2),
+            // Starts exactly when the first one stops
+            task_info(3, p0, 50, 60, 3),
+            // Zero-length tasks, alone and next to a running task
+            zero_length(4, 3, 80, 4),
+            zero_length(5, 1, 55, 5),
+        ]);
+
+        assert_eq!(state.variant_peak_concurrency(TaskID(1), VariantID(1)), 2);
+        assert_eq!(state.variant_peak_concurrency(TaskID(1), VariantID(2)), 0);
+        assert_eq!(state.variant_peak_concurrency(TaskID(1), VariantID(3)), 0);
+    }
+
+    #[test]
+    fn test_meta_spawn_latency() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(1, 1));
+        let state = build_state(vec![
+            Record::MetaDesc {
+                kind: VariantID(1),
+                message: true,
+                ordered_vc: false,
+                name: "Remote Message".to_owned(),
+            },
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 10_000, 1),
+            message_info(1, p1, 1_000, 3_000, 4_000, 1, 2),
+            message_info(1, p1, 2_000, 7_000, 8_000, 1, 3),
+            // Skewed clocks can make the message appear to start before it was sent
+            message_info(1, p1, 9_000, 8_500, 9_500, 1, 4),
+        ]);
+
+        let latencies: Vec<_> = state
+            .meta_spawn_latency()
+            .into_iter()
+            .map(|(_, latency)| latency)
+            .collect();
+        assert_eq!(latencies, vec![ts(5_000), ts(2_000), ts(0)]);
+    }
+
+    #[test]
+    fn test_device_timelines() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(0, 2));
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::GPU),
+            proc_desc(p1, ProcKind::CPU),
+            Record::GPUTaskInfo {
+                op_id: op(1),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                proc_id: p0,
+                create: ts(0),
+                ready: ts(0),
+                start: ts(0),
+                stop: ts(100),
+                gpu_start: ts(10),
+                gpu_stop: ts(20),
+                creator: None,
+                critical: None,
+                fevent: event(1),
+            },
+            task_info(2, p1, 0, 10, 2),
+        ]);
+        state.sort_time_range();
+        state.stack_time_points();
+
+        let kernel = state.procs[&p0]
+            .entries()
+            .find(|entry| matches!(entry.kind, ProcEntryKind::GPUKernel(..)))
+            .unwrap()
+            .base
+            .prof_uid;
+        let timelines = state.device_timelines();
+        assert_eq!(timelines.len(), 1);
+        let (proc_id, levels) = timelines[0];
+        assert_eq!(proc_id, p0);
+        let points: Vec<_> = levels.iter().flatten().map(|p| p.entry).collect();
+        assert_eq!(points, vec![kernel]);
+    }
+
+    #[test]
+    fn test_instances_over() {
+        let (p0, m0) = (proc_on(0, 1), mem_on(0, 1));
+        let inst = |fevent, inst_id, size| Record::InstTimelineInfo {
+            fevent: event(fevent),
+            inst_id: InstID(inst_id),
+            mem_id: m0,
+            size,
+            op_id: op(1),
+            create: ts(0),
+            ready: ts(0),
+            destroy: ts(10),
+            creator: event(1),
+        };
+        let state = build_state(vec![
+            Record::MemDesc {
+                mem_id: m0,
+                kind: MemKind::System as i32,
+                capacity: 1 << 32,
+            },
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            inst(2, 1, 1 << 30),
+            inst(3, 2, 4096),
+        ]);
+
+        let big = state.instances_over(1 << 20);
+        assert_eq!(big.len(), 1);
+        let (mem_id, inst_uid, size) = big[0];
+        assert_eq!(mem_id, m0);
+        assert_eq!(state.prof_uid_allocator.find_fevent(inst_uid), event(2));
+        assert_eq!(size, 1 << 30);
+        assert_eq!(state.instances_over(1024).len(), 2);
+    }
+
+    #[test]
+    fn test_meta_vs_app_time() {
+        let p0 = proc_on(0, 1);
+        let state = build_state(vec![
+            Record::MetaDesc {
+                kind: VariantID(1),
+                message: false,
+                ordered_vc: false,
+                name: "Task Physical Dependence Analysis".to_owned(),
+            },
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            Record::TaskWaitInfo {
+                op_id: op(1),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                wait_start: ts(20),
+                wait_ready: ts(40),
+                wait_end: ts(40),
+                wait_event: event(9),
+            },
+            Record::MetaInfo {
+                op_id: op(1),
+                lg_id: VariantID(1),
+                proc_id: p0,
+                create: ts(100),
+                ready: ts(100),
+                start: ts(100),
+                stop: ts(130),
+                creator: None,
+                critical: None,
+                fevent: event(2),
+            },
+        ]);
+
+        assert_eq!(state.procs[&p0].meta_vs_app_time(), (ts(30), ts(80)));
+    }
+
+    #[test]
+    fn test_created_by() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(0, 2));
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            with_creator(task_info(2, p0, 100, 150, 2), 1),
+            with_creator(task_info(3, p1, 20, 60, 3), 1),
+            with_creator(task_info(4, p1, 60, 90, 4), 3),
+        ]);
+
+        let uid = |op_id| state.find_task(op(op_id)).unwrap().base.prof_uid;
+        let mut children = state.created_by(uid(1));
+        children.sort();
+        let mut expected = vec![uid(2), uid(3)];
+        expected.sort();
+        assert_eq!(children, expected);
+        assert_eq!(state.created_by(uid(3)), vec![uid(4)]);
+        assert_eq!(state.created_by(uid(4)), vec![]);
+    }
+
+    #[test]
+    fn test_self_time() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            Record::MapperCallDesc {
+                kind: MapperCallKindID(1),
+                name: "map_task".to_owned(),
+            },
+            task_info(1, p0, 0, 100, 1),
+            Record::MapperCallInfo {
+                mapper_id: MapperID(0),
+                mapper_proc: p0,
+                kind: MapperCallKindID(1),
+                op_id: op(1),
+                start: ts(10),
+                stop: ts(30),
+                proc_id: p0,
+                fevent: Some(event(1)),
+            },
+            Record::TaskWaitInfo {
+                op_id: op(1),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                wait_start: ts(50),
+                wait_ready: ts(60),
+                wait_end: ts(60),
+                wait_event: event(9),
+            },
+        ]);
+        state.sort_time_range();
+
+        let task = state.find_task(op(1)).unwrap();
+        assert_eq!(task.self_time(), ts(80));
+    }
+
+    #[test]
+    fn test_write_critical_paths_json() {
+        let p0 = proc_on(0, 1);
+        let mut records = task_kind(1, 1, "foo");
+        records.extend([
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            // Task 1 creates event 3 which triggers when task 1 is done
+            trigger_info(3, 1, 1, 5),
+            with_critical(task_info(2, p0, 20, 30, 2), 3),
+        ]);
+        let mut state = build_state(records);
+        state.compute_critical_paths();
+
+        let mut out = Vec::new();
+        state.write_critical_paths_json(&mut out).unwrap();
+        let paths: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let task1 = state.find_task(op(1)).unwrap().base.prof_uid;
+        let task2 = state.find_task(op(2)).unwrap().base.prof_uid;
+        assert_eq!(
+            paths,
+            serde_json::json!([{
+                "prof_uid": task2.0,
+                "name": "foo <2>",
+                "completion": 30,
+                "critical_path": [{"prof_uid": task1.0, "time": 10}],
+            }])
+        );
+    }
+
+    #[test]
+    fn test_mem_kind_host_device() {
+        let device = [
+            MemKind::Framebuffer,
+            MemKind::ZeroCopy,
+            MemKind::GPUManaged,
+            MemKind::GPUDynamic,
+        ];
+        let host = [MemKind::System, MemKind::Registered, MemKind::Socket];
+        for kind in (0..=14).map(|k| MemKind::try_from(k).unwrap()) {
+            assert_eq!(kind.is_device(), device.contains(&kind), "{:?}", kind);
+            assert_eq!(kind.is_host(), host.contains(&kind), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_op_and_task_counts() {
+        let p0 = proc_on(0, 1);
+        let state = build_state(vec![
+            Record::MetaDesc {
+                kind: VariantID(1),
+                message: false,
+                ordered_vc: false,
+                name: "Task Physical Dependence Analysis".to_owned(),
+            },
+            Record::OperationInstance {
+                op_id: op(3),
+                parent_id: None,
+                kind: 0,
+                provenance: None,
+            },
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p0, 10, 20, 2),
+            Record::MetaInfo {
+                op_id: op(3),
+                lg_id: VariantID(1),
+                proc_id: p0,
+                create: ts(20),
+                ready: ts(20),
+                start: ts(20),
+                stop: ts(30),
+                creator: None,
+                critical: None,
+                fevent: event(3),
+            },
+        ]);
+
+        assert_eq!(state.op_count(), 3);
+        assert_eq!(state.task_count(), 2);
+    }
+
+    #[test]
+    fn test_restrict_to_op_subtree() {
+        let op_instance = |op_id, parent_id: Option<u64>| Record::OperationInstance {
+            op_id: op(op_id),
+            parent_id: parent_id.map(op),
+            kind: 0,
+            provenance: None,
+        };
+        let (p0, p1, p2) = (proc_on(0, 1), proc_on(0, 2), proc_on(0, 3));
+        let (m0, m1, m2) = (mem_on(0, 1), mem_on(0, 2), mem_on(0, 3));
+        let mut state = build_state(vec![
+            op_instance(1, None),
+            op_instance(2, Some(1)),
+            op_instance(3, Some(2)),
+            op_instance(4, None),
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            proc_desc(p2, ProcKind::CPU),
+            task_info(2, p0, 0, 10, 1),
+            task_info(3, p1, 10, 20, 2),
+            task_info(4, p2, 0, 20, 3),
+            copy_info(3, 0, 10, 4),
+            copy_inst_info(m0, m1, 10, 11, 4),
+            copy_info(4, 0, 10, 5),
+            copy_inst_info(m0, m2, 10, 12, 5),
+        ]);
+        state.restrict_to_op_subtree(op(1));
+
+        assert!(state.procs[&p0].is_visible());
+        assert!(state.procs[&p1].is_visible());
+        assert!(!state.procs[&p2].is_visible());
+        assert!(state.chans[&ChanID::new_copy(m0, m1)].is_visible());
+        assert!(!state.chans[&ChanID::new_copy(m0, m2)].is_visible());
+    }
+
+    #[test]
+    fn test_chan_average_queue_depth() {
+        let (m0, m1) = (mem_on(0, 1), mem_on(0, 2));
+        let mut state = build_state(vec![
+            copy_info(1, 0, 40, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            copy_info(2, 20, 60, 2),
+            copy_inst_info(m0, m1, 10, 11, 2),
+        ]);
+        state.sort_time_range();
+
+        let chan = &state.chans[&ChanID::new_copy(m0, m1)];
+        assert!((chan.average_queue_depth(ts(100)) - 0.8).abs() < 1e-9);
+        assert!((chan.average_queue_depth(ts(40)) - 1.5).abs() < 1e-9);
+        let empty = Chan::new(ChanID::new_fill(m0));
+        assert_eq!(empty.average_queue_depth(ts(100)), 0.0);
+    }
+
+    #[test]
+    fn test_try_find_fevent() {
+        let p0 = proc_on(0, 1);
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            Record::MapperCallDesc {
+                kind: MapperCallKindID(1),
+                name: "map_task".to_owned(),
+            },
+            task_info(1, p0, 0, 100, 1),
+            Record::MapperCallInfo {
+                mapper_id: MapperID(0),
+                mapper_proc: p0,
+                kind: MapperCallKindID(1),
+                op_id: op(1),
+                start: ts(10),
+                stop: ts(30),
+                proc_id: p0,
+                fevent: Some(event(1)),
+            },
+        ]);
+
+        let proc = &state.procs[&p0];
+        let task = proc.find_task(op(1)).unwrap().base.prof_uid;
+        let call = proc
+            .entries()
+            .find(|entry| matches!(entry.kind, ProcEntryKind::MapperCall(..)))
+            .unwrap()
+            .base
+            .prof_uid;
+        assert_eq!(state.try_find_fevent(task), Some(event(1)));
+        assert_eq!(state.try_find_fevent(call), None);
+    }
+
+    #[test]
+    fn test_write_utilization_csv() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 25, 1),
+            task_info(2, p0, 50, 90, 2),
+            Record::TaskWaitInfo {
+                op_id: op(2),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                wait_start: ts(75),
+                wait_ready: ts(85),
+                wait_end: ts(85),
+                wait_event: event(9),
+            },
+            task_info(3, p0, 90, 100, 3),
+        ]);
+        state.sort_time_range();
+
+        let mut out = Vec::new();
+        state.write_utilization_csv(4, &mut out).unwrap();
+        let name = state.procs[&p0].name(&state);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("proc,0,25,50,75\n{},1,0,1,0.6\n", name)
+        );
+    }
+
+    #[test]
+    fn test_validate_critical_acyclic() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            trigger_info(3, 1, 1, 5),
+            with_critical(task_info(2, p0, 20, 30, 2), 3),
+        ]);
+        state.compute_critical_paths();
+        assert!(state.validate_critical_acyclic());
+
+        // Corrupt the graph so that two events point at each other
+        let v1 = state.event_lookup[&event(1)];
+        let v3 = state.event_lookup[&event(3)];
+        state.event_graph[v1].critical = Some(v3);
+        state.event_graph[v3].critical = Some(v1);
+        assert!(!state.validate_critical_acyclic());
+    }
+
+    #[test]
+    fn test_from_records() {
+        init_config();
+        let p0 = proc_on(0, 1);
+        let state = State::from_records(
+            [proc_desc(p0, ProcKind::CPU), task_info(1, p0, 0, 10, 1)],
+            Timestamp::ZERO,
+        )
+        .unwrap();
+
+        assert_eq!(state.task_count(), 1);
+        assert_eq!(state.last_time, ts(10));
+        assert!(state.find_task(op(1)).is_some());
+
+        let bad = State::from_records(
+            [Record::ProcDesc {
+                proc_id: p0,
+                kind: 42,
+                cuda_device_uuid: Uuid(Vec::new()),
+            }],
+            Timestamp::ZERO,
+        );
+        assert_eq!(bad.err(), Some(ParseError::BadProcKind(42)));
+    }
+
+    #[test]
+    fn test_task_scheduling_delays() {
+        let p0 = proc_on(0, 1);
+        let delayed = |op_id, ready, start, stop, fevent| {
+            let mut record = task_info(op_id, p0, start, stop, fevent);
+            if let Record::TaskInfo {
+                create,
+                ready: record_ready,
+                ..
+            } = &mut record
+            {
+                *create = ts(ready);
+                *record_ready = ts(ready);
+            }
+            record
+        };
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            delayed(1, 0, 10, 20, 1),
+            delayed(2, 5, 35, 40, 2),
+        ]);
+
+        let mut delays = state.task_scheduling_delays();
+        delays.sort();
+        assert_eq!(delays, vec![ts(10), ts(30)]);
+        assert_eq!(
+            state.task_scheduling_delay_summary(),
+            Some((ts(20), ts(30)))
+        );
+    }
+
+    #[test]
+    fn test_trim_time_range_counting() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p0, 20, 40, 2),
+            task_info(3, p0, 50, 100, 3),
+        ]);
+
+        let dropped = state.trim_time_range_counting(Some(ts(15)), Some(ts(60)));
+        assert_eq!(dropped, 1);
+        assert_eq!(state.task_count(), 2);
+        assert_eq!(state.last_time, ts(45));
+        assert_eq!(state.trim_time_range_counting(None, None), 0);
+    }
+
+    #[test]
+    fn test_instance_redistrict_chain() {
+        let (p0, m0) = (proc_on(0, 1), mem_on(0, 1));
+        let inst = |fevent, inst_id, create, destroy| Record::InstTimelineInfo {
+            fevent: event(fevent),
+            inst_id: InstID(inst_id),
+            mem_id: m0,
+            size: 64,
+            op_id: op(1),
+            create: ts(create),
+            ready: ts(create),
+            destroy: ts(destroy),
+            creator: event(1),
+        };
+        let redistrict = |result, previous, next, performed| Record::InstanceRedistrictInfo {
+            result: event(result),
+            precondition: None,
+            previous: event(previous),
+            next: event(next),
+            performed: ts(performed),
+        };
+        let state = build_state(vec![
+            Record::MemDesc {
+                mem_id: m0,
+                kind: MemKind::System as i32,
+                capacity: 1 << 30,
+            },
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            inst(2, 1, 0, 10),
+            inst(3, 2, 10, 20),
+            inst(4, 3, 20, 30),
+            redistrict(5, 2, 3, 10),
+            redistrict(6, 3, 4, 20),
+        ]);
+
+        let uid = |fevent| {
+            *state
+                .insts
+                .keys()
+                .find(|inst_uid| state.find_fevent(**inst_uid) == event(fevent))
+                .unwrap()
+        };
+        let chain: Vec<_> = state
+            .instance_redistrict_chain(uid(4))
+            .into_iter()
+            .map(|inst_uid| state.find_fevent(inst_uid))
+            .collect();
+        assert_eq!(chain, vec![event(2), event(3), event(4)]);
+        assert_eq!(state.instance_redistrict_chain(uid(2)), vec![uid(2)]);
+    }
+
+    #[test]
+    fn test_op_bytes_moved() {
+        let (m0, m1, m2) = (mem_on(0, 1), mem_on(0, 2), mem_on(1, 1));
+        let state = build_state(vec![
+            copy_info(1, 0, 10, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            copy_info(1, 20, 30, 2),
+            copy_inst_info(m1, m2, 11, 12, 2),
+            fill_info(1, 40, 50, 3),
+            fill_inst_info(m0, 10, 3),
+            copy_info(2, 0, 10, 4),
+            copy_inst_info(m0, m1, 10, 11, 4),
+        ]);
+
+        assert_eq!(state.op_bytes_moved(op(1)), 3072);
+        assert_eq!(state.op_bytes_moved(op(2)), 1024);
+        assert_eq!(state.op_bytes_moved(op(3)), 0);
+    }
+
+    #[test]
+    fn test_to_records_round_trip() {
+        let mut records = task_kind(1, 1, "t");
+        records.extend([
+            Record::ProcDesc {
+                proc_id: proc_on(0, 0),
+                kind: ProcKind::CPU as i32,
+                cuda_device_uuid: Uuid(Vec::new()),
+            },
+            Record::MemDesc {
+                mem_id: mem_on(0, 0),
+                kind: MemKind::System as i32,
+                capacity: 1 << 20,
+            },
+            task_info(1, proc_on(0, 0), 10, 20, 1),
+            task_info(2, proc_on(0, 0), 30, 40, 2),
+            task_info(3, proc_on(0, 0), 50, 60, 3),
+            Record::InstTimelineInfo {
+                fevent: event(4),
+                inst_id: InstID(1),
+                mem_id: mem_on(0, 0),
+                size: 256,
+                op_id: op(1),
+                create: ts(10),
+                ready: ts(12),
+                destroy: ts(50),
+                creator: event(1),
+            },
+        ]);
+        records.push(Record::TaskWaitInfo {
+            op_id: op(2),
+            task_id: TaskID(1),
+            variant_id: VariantID(1),
+            wait_start: ts(32),
+            wait_ready: ts(34),
+            wait_end: ts(35),
+            wait_event: event(1),
+        });
+        let state = build_state(records);
+        let rebuilt = State::from_records(state.to_records(), Timestamp::ZERO).unwrap();
+        assert_eq!(rebuilt.task_count(), state.task_count());
+        assert_eq!(rebuilt.task_count(), 3);
+        let proc = rebuilt.procs.get(&proc_on(0, 0)).unwrap();
+        assert_eq!(proc.kind, Some(ProcKind::CPU));
+        let waits: usize = proc
+            .entries
+            .values()
+            .map(|entry| entry.waiters.wait_intervals.len())
+            .sum();
+        assert_eq!(waits, 1);
+        let mem = rebuilt.mems.get(&mem_on(0, 0)).unwrap();
+        assert_eq!(mem.insts.len(), 1);
+        assert_eq!(mem.insts.values().next().unwrap().size, Some(256));
+    }
+
+    #[test]
+    fn test_to_records_round_trip_calls() {
+        let p0 = proc_on(0, 1);
+        let mut records = task_kind(1, 1, "t");
+        records.extend([
+            proc_desc(p0, ProcKind::CPU),
+            Record::RuntimeCallDesc {
+                kind: RuntimeCallKindID(1),
+                name: "runtime".to_owned(),
+            },
+            Record::MapperCallDesc {
+                kind: MapperCallKindID(1),
+                name: "map_task".to_owned(),
+            },
+            task_info(1, p0, 0, 100, 1),
+            Record::RuntimeCallInfo {
+                kind: RuntimeCallKindID(1),
+                start: ts(10),
+                stop: ts(50),
+                proc_id: p0,
+                fevent: Some(event(1)),
+            },
+            Record::MapperCallInfo {
+                mapper_id: MapperID(0),
+                mapper_proc: p0,
+                kind: MapperCallKindID(1),
+                op_id: op(1),
+                start: ts(20),
+                stop: ts(30),
+                proc_id: p0,
+                fevent: Some(event(1)),
+            },
+            // This wait ends up inside the mapper call
+            Record::TaskWaitInfo {
+                op_id: op(1),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                wait_start: ts(22),
+                wait_ready: ts(26),
+                wait_end: ts(28),
+                wait_event: event(9),
+            },
+            Record::ProfTaskInfo {
+                proc_id: p0,
+                op_id: op(1),
+                start: ts(100),
+                stop: ts(110),
+                creator: event(1),
+                fevent: event(20),
+                completion: true,
+            },
+            Record::MemDesc {
+                mem_id: mem_on(0, 1),
+                kind: MemKind::System as i32,
+                capacity: 1 << 20,
+            },
+            Record::InstTimelineInfo {
+                fevent: event(4),
+                inst_id: InstID(1),
+                mem_id: mem_on(0, 1),
+                size: 256,
+                op_id: op(1),
+                create: ts(0),
+                ready: ts(12),
+                destroy: ts(90),
+                creator: event(1),
+            },
+            // Allocation response for the instance
+            Record::ProfTaskInfo {
+                proc_id: p0,
+                op_id: op(1),
+                start: ts(5),
+                stop: ts(6),
+                creator: event(4),
+                fevent: event(21),
+                completion: false,
+            },
+        ]);
+        let mut state = build_state(records);
+        state.sort_time_range();
+        let mut rebuilt = State::from_records(state.to_records(), Timestamp::ZERO).unwrap();
+        rebuilt.sort_time_range();
+
+        let summarize = |state: &State| {
+            let proc = &state.procs[&p0];
+            let task = proc.find_task(op(1)).unwrap().base.prof_uid;
+            let mut nesting = Vec::new();
+            let mut stack = vec![(task, 0)];
+            while let Some((uid, depth)) = stack.pop() {
+                let entry = proc.find_entry(uid).unwrap();
+                let waits: Vec<_> = entry
+                    .waiters
+                    .wait_intervals
+                    .iter()
+                    .filter(|wait| wait.callee.is_none())
+                    .map(|wait| (wait.start, wait.end, wait.event))
+                    .collect();
+                nesting.push((depth, entry.kind, entry.time_range.start, waits));
+                for callee in proc.subcalls_of(uid) {
+                    stack.push((callee, depth + 1));
+                }
+            }
+            let kinds: Vec<_> = proc.entries.values().map(|entry| entry.kind).collect();
+            (kinds, nesting)
+        };
+        let (kinds, nesting) = summarize(&rebuilt);
+        assert_eq!(kinds.len(), 5);
+        assert!(kinds.contains(&ProcEntryKind::ProfTask));
+        let inst = rebuilt.mems[&mem_on(0, 1)].insts.values().next().unwrap();
+        assert_eq!(inst.time_range.spawn, Some(ts(5)));
+        assert_eq!(nesting.len(), 3);
+        assert_eq!(nesting[2].3, vec![(ts(22), ts(28), Some(event(9)))]);
+        assert_eq!((kinds, nesting), summarize(&state));
+    }
+
+    #[test]
+    fn test_color_by_name() {
+        let mut records = task_kind(1, 1, "foo");
+        records.extend(task_kind(2, 1, "bar"));
+        let mut first = build_state(records);
+        let mut records = task_kind(7, 3, "foo");
+        records.extend(task_kind(1, 1, "baz"));
+        let mut second = build_state(records);
+        first.assign_colors_impl(true);
+        second.assign_colors_impl(true);
+        let color = |state: &State, key| state.variants.get(&key).unwrap().color;
+        assert!(color(&first, (TaskID(1), VariantID(1))).is_some());
+        assert_eq!(
+            color(&first, (TaskID(1), VariantID(1))),
+            color(&second, (TaskID(7), VariantID(3)))
+        );
+    }
+
+    #[test]
+    fn test_empty_procs() {
+        let mut records = task_kind(1, 1, "t");
+        records.push(Record::ProcDesc {
+            proc_id: proc_on(0, 1),
+            kind: ProcKind::CPU as i32,
+            cuda_device_uuid: Uuid(Vec::new()),
+        });
+        records.push(task_info(1, proc_on(0, 0), 10, 20, 1));
+        let state = build_state(records);
+        assert_eq!(state.empty_procs(), vec![proc_on(0, 1)]);
+    }
+
+    #[test]
+    fn test_critical_contribution_by_variant() {
+        let p0 = proc_on(0, 1);
+        let other_variant = |record| match record {
+            Record::TaskInfo {
+                op_id,
+                proc_id,
+                create,
+                ready,
+                start,
+                stop,
+                creator,
+                critical,
+                fevent,
+                ..
+            } => Record::TaskInfo {
+                op_id,
+                task_id: TaskID(2),
+                variant_id: VariantID(2),
+                proc_id,
+                create,
+                ready,
+                start,
+                stop,
+                creator,
+                critical,
+                fevent,
+            },
+            _ => unreachable!(),
+        };
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            trigger_info(3, 1, 1, 5),
+            other_variant(with_critical(task_info(2, p0, 20, 30, 2), 3)),
+            with_critical(task_info(3, p0, 40, 60, 4), 2),
+            // Not on the longest chain
+            other_variant(with_critical(task_info(4, p0, 15, 25, 5), 1)),
+        ]);
+        state.compute_critical_paths();
+
+        let contribution = state.critical_contribution_by_variant();
+        assert_eq!(contribution.len(), 2);
+        assert_eq!(contribution[&(TaskID(1), VariantID(1))], ts(30));
+        assert_eq!(contribution[&(TaskID(2), VariantID(2))], ts(10));
+    }
+
+    #[test]
+    fn test_final_task() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(0, 2));
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 50, 1),
+            task_info(2, p1, 10, 80, 2),
+            task_info(3, p0, 60, 70, 3),
+        ]);
+        let last = state.final_task().unwrap();
+        assert_eq!(
+            state.procs[&p1].find_task(op(2)).unwrap().base.prof_uid,
+            last
+        );
+    }
+
+    #[test]
+    fn test_message_variant_time() {
+        let p0 = proc_on(0, 1);
+        let meta_desc = |kind, message| Record::MetaDesc {
+            kind: VariantID(kind),
+            message,
+            ordered_vc: false,
+            name: format!("meta {}", kind),
+        };
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::Utility),
+            meta_desc(1, true),
+            meta_desc(2, false),
+            message_info(1, p0, 0, 10, 25, 1, 2),
+            Record::MetaInfo {
+                op_id: op(2),
+                lg_id: VariantID(2),
+                proc_id: p0,
+                create: ts(30),
+                ready: ts(30),
+                start: ts(30),
+                stop: ts(70),
+                creator: None,
+                critical: None,
+                fevent: event(3),
+            },
+        ]);
+        assert_eq!(state.message_variant_time(), ts(15));
+    }
+
+    #[test]
+    fn test_compute_max_levels() {
+        let mut points = Vec::new();
+        for (entry, (start, stop)) in [(0, 10), (5, 15), (12, 20)].into_iter().enumerate() {
+            points.push(TimePoint::new(ts(start), entry, true, 0));
+            points.push(TimePoint::new(ts(stop), entry, false, 0));
+        }
+        assert_eq!(compute_max_levels(&points), 2);
+        assert_eq!(compute_max_levels::<usize, u64>(&[]), 0);
+    }
+
+    #[test]
+    fn test_mark_noncritical_hidden() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            trigger_info(3, 1, 1, 5),
+            with_critical(task_info(2, p0, 20, 30, 2), 3),
+            with_critical(task_info(3, p0, 40, 60, 4), 2),
+            // Off the critical chain
+            with_critical(task_info(4, p0, 15, 25, 5), 1),
+        ]);
+        state.sort_time_range();
+        state.compute_critical_paths();
+        assert!(state.mark_noncritical_hidden());
+
+        let proc = &state.procs[&p0];
+        let hidden = |op_id| proc.find_task(op(op_id)).unwrap().base.hidden;
+        assert!(!hidden(1));
+        assert!(!hidden(2));
+        assert!(!hidden(3));
+        assert!(hidden(4));
+        assert_eq!(proc.time_points(None).len(), 3);
+
+        // Without critical path data nothing gets hidden
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            task_info(2, p0, 20, 30, 2),
+        ]);
+        state.sort_time_range();
+        state.compute_critical_paths();
+        assert!(!state.mark_noncritical_hidden());
+        let proc = &state.procs[&p0];
+        assert!(proc.entries.values().all(|entry| !entry.base.hidden));
+        assert_eq!(proc.time_points(None).len(), 2);
+    }
+
+    #[test]
+    fn test_collective_copy_groups() {
+        let (m0, m1, m2) = (mem_on(0, 1), mem_on(1, 1), mem_on(2, 1));
+        let collective = |mut record, id| {
+            if let Record::CopyInfo { collective, .. } = &mut record {
+                *collective = id;
+            }
+            record
+        };
+        let state = build_state(vec![
+            collective(copy_info(1, 0, 10, 1), 7),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            collective(copy_info(1, 0, 12, 2), 7),
+            copy_inst_info(m0, m2, 10, 12, 2),
+            copy_info(2, 20, 30, 3),
+            copy_inst_info(m1, m2, 11, 12, 3),
+        ]);
+
+        let groups = state.collective_copy_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[&7].len(), 2);
+    }
+
+    #[test]
+    fn test_total_wait_time() {
+        let p0 = proc_on(0, 1);
+        let wait = |start, end| Record::TaskWaitInfo {
+            op_id: op(1),
+            task_id: TaskID(1),
+            variant_id: VariantID(1),
+            wait_start: ts(start),
+            wait_ready: ts(end),
+            wait_end: ts(end),
+            wait_event: event(2),
+        };
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            wait(10, 20),
+            wait(50, 75),
+        ]);
+        assert_eq!(state.procs[&p0].total_wait_time(), ts(35));
+    }
+
+    #[test]
+    fn test_op_kind_counts() {
+        let op_instance = |op_id, kind| Record::OperationInstance {
+            op_id: op(op_id),
+            parent_id: None,
+            kind,
+            provenance: None,
+        };
+        let op_desc = |kind, name: &str| Record::OpDesc {
+            kind,
+            name: name.to_owned(),
+        };
+        let state = build_state(vec![
+            op_desc(1, "Copy"),
+            op_desc(2, "Fill"),
+            op_instance(1, 1),
+            op_instance(2, 1),
+            op_instance(3, 2),
+            Record::MultiTask {
+                op_id: op(4),
+                task_id: TaskID(1),
+            },
+        ]);
+
+        let counts = state.op_kind_counts();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["Copy"], 2);
+        assert_eq!(counts["Fill"], 1);
+        assert_eq!(counts["Unknown"], 1);
+    }
+
+    #[test]
+    fn test_timestamp_serialize() {
+        assert_eq!(serde_json::to_string(&ts(1234)).unwrap(), "1234");
+        assert_eq!(
+            serde_json::to_string(&vec![Timestamp::ZERO, ts(5)]).unwrap(),
+            "[0,5]"
+        );
+    }
+
+    #[test]
+    fn test_upstream_tasks() {
+        let p0 = proc_on(0, 1);
+        // Diamond: task 1 -> tasks 2 and 3 -> merge 10 -> task 4
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            with_critical(task_info(2, p0, 10, 20, 2), 1),
+            with_critical(task_info(3, p0, 10, 30, 3), 1),
+            Record::EventMergerInfo {
+                result: event(10),
+                fevent: event(1),
+                performed: ts(5),
+                pre0: Some(event(2)),
+                pre1: Some(event(3)),
+                pre2: None,
+                pre3: None,
+            },
+            with_critical(task_info(4, p0, 30, 40, 4), 10),
+            // Independent of task 4
+            task_info(5, p0, 40, 50, 5),
+        ]);
+        let uid = |op_id| state.find_task(op(op_id)).unwrap().base.prof_uid;
+
+        let mut expected = vec![uid(1), uid(2), uid(3)];
+        expected.sort();
+        assert_eq!(state.upstream_tasks(uid(4)), expected);
+        assert_eq!(state.upstream_tasks(uid(1)), Vec::new());
+    }
+
+    #[test]
+    fn test_min_task_duration() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::GPU),
+            Record::RuntimeCallDesc {
+                kind: RuntimeCallKindID(1),
+                name: "bar".to_owned(),
+            },
+            Record::GPUTaskInfo {
+                op_id: op(1),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                proc_id: p0,
+                create: ts(0),
+                ready: ts(0),
+                start: ts(0),
+                stop: ts(10),
+                gpu_start: ts(5),
+                gpu_stop: ts(9),
+                creator: None,
+                critical: None,
+                fevent: event(1),
+            },
+            Record::TaskWaitInfo {
+                op_id: op(1),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                wait_start: ts(2),
+                wait_ready: ts(4),
+                wait_end: ts(4),
+                wait_event: event(3),
+            },
+            Record::RuntimeCallInfo {
+                kind: RuntimeCallKindID(1),
+                start: ts(1),
+                stop: ts(5),
+                proc_id: p0,
+                fevent: Some(event(1)),
+            },
+            task_info(2, p0, 20, 500, 2),
+        ]);
+        state.sort_time_range_impl(ts(100));
+
+        let short = state.find_task(op(1)).unwrap();
+        let long = state.find_task(op(2)).unwrap();
+        assert!(short.base.hidden);
+        assert!(!long.base.hidden);
+        let proc = &state.procs[&p0];
+        for entry in proc.entries.values() {
+            let hidden =
+                !matches!(entry.kind, ProcEntryKind::Task(..)) || entry.op_id == Some(op(1));
+            assert_eq!(entry.base.hidden, hidden);
+        }
+        assert_eq!(proc.entries.len(), 4);
+        let visible: Vec<_> = proc.time_points(None).iter().map(|p| p.entry).collect();
+        assert_eq!(visible, vec![long.base.prof_uid]);
+        assert!(proc.time_points(Some(DeviceKind::Device)).is_empty());
+        // Hidden entries no longer count towards utilization
+        assert!(
+            proc.util_time_points(None)
+                .iter()
+                .all(|p| p.entry == long.base.prof_uid)
+        );
+        assert!(proc.util_time_points(Some(DeviceKind::Device)).is_empty());
+        assert_eq!(proc.busy_time(None), ts(480));
+        // The hidden task still owns its completion event
+        let node = state.event_lookup[&event(1)];
+        assert_eq!(state.event_graph[node].kind, EventEntryKind::TaskEvent);
+        assert!(state.operations.contains_key(&op(1)));
+    }
+
+    #[test]
+    fn test_copy_kind_counts() {
+        let (m0, m1) = (mem_on(0, 1), mem_on(1, 2));
+        let state = build_state(vec![
+            copy_info(1, 0, 10, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            copy_info(2, 20, 30, 2),
+            copy_inst_info(m0, m1, 10, 11, 2),
+        ]);
+
+        let counts = state.chans[&ChanID::new_copy(m0, m1)].copy_kind_counts();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[&CopyKind::Copy], 2);
+    }
+
+    #[test]
+    fn test_effective_parallelism() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(0, 2));
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            task_info(2, p1, 0, 100, 2),
+        ]);
+        assert!((state.effective_parallelism() - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_invalid_instances() {
+        let m0 = mem_on(0, 1);
+        let inst = |fevent, create, ready, destroy| Record::InstTimelineInfo {
+            fevent: event(fevent),
+            inst_id: InstID(fevent),
+            mem_id: m0,
+            size: 64,
+            op_id: op(1),
+            create: ts(create),
+            ready: ts(ready),
+            destroy: ts(destroy),
+            creator: event(1),
+        };
+        let state = build_state(vec![
+            Record::MemDesc {
+                mem_id: m0,
+                kind: MemKind::System as i32,
+                capacity: 1 << 30,
+            },
+            inst(2, 0, 5, 10),
+            // Ready before it was created
+            inst(3, 8, 5, 10),
+        ]);
+
+        let invalid = state.invalid_instances();
+        assert_eq!(invalid.len(), 1);
+        let inst = state.find_inst(invalid[0]).unwrap();
+        assert_eq!(inst.inst_id, Some(InstID(3)));
+        assert!(!inst.validate_times());
+    }
+
+    #[test]
+    fn test_op_span() {
+        let p0 = proc_on(0, 1);
+        let (m0, m1) = (mem_on(0, 1), mem_on(1, 2));
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 10, 40, 1),
+            copy_info(1, 35, 70, 2),
+            copy_inst_info(m0, m1, 10, 11, 2),
+            task_info(2, p0, 80, 90, 3),
+        ]);
+        assert_eq!(state.op_span(op(1)), Some((ts(10), ts(70))));
+        assert_eq!(state.op_span(op(2)), Some((ts(80), ts(90))));
+        assert_eq!(state.op_span(op(3)), None);
+    }
+
+    #[test]
+    fn test_write_instances_json() {
+        let m0 = mem_on(0, 1);
+        let mut state = build_state(vec![
+            Record::MemDesc {
+                mem_id: m0,
+                kind: MemKind::System as i32,
+                capacity: 1 << 30,
+            },
+            Record::InstTimelineInfo {
+                fevent: event(2),
+                inst_id: InstID(7),
+                mem_id: m0,
+                size: 64,
+                op_id: op(1),
+                create: ts(5),
+                ready: ts(10),
+                destroy: ts(30),
+                creator: event(1),
+            },
+        ]);
+        state.sort_time_range();
+
+        let mut out = Vec::new();
+        state.mems[&m0]
+            .write_instances_json(&state, &mut out)
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let insts = json.as_array().unwrap();
+        assert_eq!(insts.len(), 1);
+        assert_eq!(insts[0]["size"], 64);
+        assert_eq!(insts[0]["level"], 1);
+        assert_eq!(insts[0]["create"], 5);
+        assert_eq!(insts[0]["ready"], 10);
+        assert_eq!(insts[0]["destroy"], 30);
+        assert!(insts[0]["name"].is_string());
+    }
+
+    #[test]
+    fn test_busiest_proc() {
+        let (p0, p1, p2) = (proc_on(0, 1), proc_on(0, 2), proc_on(0, 3));
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            proc_desc(p2, ProcKind::IO),
+            task_info(1, p0, 0, 25, 1),
+            task_info(2, p1, 0, 75, 2),
+            task_info(3, p1, 75, 80, 3),
+            task_info(4, p2, 0, 100, 4),
+        ]);
+        state.sort_time_range();
+        let (proc_id, fraction) = state.busiest_proc().unwrap();
+        assert_eq!(proc_id, p1);
+        assert!((fraction - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_critical_path_waits() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 10, 1),
+            Record::TaskWaitInfo {
+                op_id: op(1),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                wait_start: ts(2),
+                wait_ready: ts(6),
+                wait_end: ts(6),
+                wait_event: event(9),
+            },
+            trigger_info(3, 1, 1, 5),
+            with_critical(task_info(2, p0, 20, 30, 2), 3),
+            with_critical(task_info(3, p0, 40, 60, 4), 2),
+        ]);
+        state.compute_critical_paths();
+        let uid = |op_id| state.find_task(op(op_id)).unwrap().base.prof_uid;
+
+        assert_eq!(
+            state.critical_path_waits(uid(3)),
+            vec![(uid(1), ts(2), ts(6), event(9))]
+        );
+        assert_eq!(state.critical_path_waits(uid(1)).len(), 1);
+    }
+
+    #[test]
+    fn test_allocation_contention_score() {
+        let p0 = proc_on(0, 1);
+        let m0 = mem_on(0, 1);
+        let inst = |fevent, ready| Record::InstTimelineInfo {
+            fevent: event(fevent),
+            inst_id: InstID(fevent),
+            mem_id: m0,
+            size: 64,
+            op_id: op(1),
+            create: ts(0),
+            ready: ts(ready),
+            destroy: ts(100),
+            creator: event(1),
+        };
+        let allocation = |inst_event, start, fevent| Record::ProfTaskInfo {
+            proc_id: p0,
+            op_id: op(1),
+            start: ts(start),
+            stop: ts(start + 1),
+            creator: event(inst_event),
+            fevent: event(fevent),
+            completion: false,
+        };
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::Utility),
+            Record::MemDesc {
+                mem_id: m0,
+                kind: MemKind::System as i32,
+                capacity: 1 << 30,
+            },
+            // Ready as soon as it was allocated
+            inst(2, 10),
+            allocation(2, 20, 4),
+            // Ready long after it was allocated
+            inst(3, 30),
+            allocation(3, 15, 5),
+        ]);
+        assert_eq!(state.mems[&m0].allocation_contention_score(), 0.5);
+    }
+
+    #[test]
+    fn test_proc_first_tasks() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(0, 2));
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            task_info(1, p0, 30, 40, 1),
+            task_info(2, p0, 10, 20, 2),
+        ]);
+        let uid = |state: &State, op_id| state.find_task(op(op_id)).unwrap().base.prof_uid;
+
+        let first = state.proc_first_tasks();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[&p0], uid(&state, 2));
+
+        state.sort_time_range();
+        assert_eq!(state.proc_first_tasks()[&p0], uid(&state, 2));
+    }
+
+    #[test]
+    fn test_runtime_config_describe() {
+        let config = RuntimeConfig {
+            spy: true,
+            safe_ctrlrepl: true,
+            ..Default::default()
+        };
+        assert_eq!(config.describe(), vec!["Legion Spy", "-lg:safe_ctrlrepl"]);
+        assert_eq!(config.to_string(), "Legion Spy, -lg:safe_ctrlrepl");
+        assert!(RuntimeConfig::default().describe().is_empty());
+    }
+
+    #[test]
+    fn test_variants_of() {
+        let mut records = task_kind(1, 1, "cpu");
+        records.extend(task_kind(1, 2, "gpu"));
+        records.extend(task_kind(2, 3, "other"));
+        let state = build_state(records);
+        assert_eq!(
+            state.variants_of(TaskID(1)),
+            vec![VariantID(1), VariantID(2)]
+        );
+        assert_eq!(state.variants_of(TaskID(3)), Vec::new());
+    }
+
+    #[test]
+    fn test_calibration_error() {
+        let state = build_state(vec![Record::CalibrationErr {
+            calibration_err: -5000,
+        }]);
+        assert_eq!(state.calibration_error(), -5000);
+        assert!(state.has_large_calibration_error());
+
+        let state = build_state(vec![Record::CalibrationErr {
+            calibration_err: 200,
+        }]);
+        assert!(!state.has_large_calibration_error());
+    }
+
+    #[test]
+    fn test_live_bytes_at() {
+        let m0 = mem_on(0, 1);
+        let inst = |fevent, size, ready, destroy| Record::InstTimelineInfo {
+            fevent: event(fevent),
+            inst_id: InstID(fevent),
+            mem_id: m0,
+            size,
+            op_id: op(1),
+            create: ts(0),
+            ready: ts(ready),
+            destroy: ts(destroy),
+            creator: event(1),
+        };
+        let state = build_state(vec![
+            Record::MemDesc {
+                mem_id: m0,
+                kind: MemKind::System as i32,
+                capacity: 1 << 30,
+            },
+            inst(2, 64, 10, 50),
+            inst(3, 256, 60, 90),
+        ]);
+        let mem = &state.mems[&m0];
+        assert_eq!(mem.live_bytes_at(ts(30)), 64);
+        assert_eq!(mem.live_bytes_at(ts(50)), 0);
+        assert_eq!(mem.live_bytes_at(ts(60)), 256);
+        assert_eq!(mem.live_bytes_at(ts(5)), 0);
+    }
+
+    #[test]
+    fn test_runtime_call_time() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(0, 2));
+        let call = |start, stop| Record::RuntimeCallInfo {
+            kind: RuntimeCallKindID(2),
+            start: ts(start),
+            stop: ts(stop),
+            proc_id: p0,
+            fevent: None,
+        };
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::Utility),
+            proc_desc(p1, ProcKind::Utility),
+            Record::RuntimeCallDesc {
+                kind: RuntimeCallKindID(2),
+                name: "runtime".to_owned(),
+            },
+            call(10, 25),
+            call(40, 45),
+            task_info(1, p1, 0, 100, 1),
+        ]);
+
+        assert_eq!(state.procs[&p0].runtime_call_time(), ts(20));
+        let overhead = state.runtime_overhead_by_proc();
+        assert_eq!(overhead.len(), 1);
+        assert_eq!(overhead[&p0], ts(20));
+    }
+
+    #[test]
+    fn test_events_of_kind() {
+        let merge = |result, pre0, pre1| Record::EventMergerInfo {
+            result: event(result),
+            fevent: event(1),
+            performed: ts(5),
+            pre0: Some(event(pre0)),
+            pre1: Some(event(pre1)),
+            pre2: None,
+            pre3: None,
+        };
+        let external = |external, fevent, provenance| Record::ExternalEventInfo {
+            external: event(external),
+            fevent: event(fevent),
+            performed: ts(5),
+            triggered: ts(6),
+            provenance: ProvenanceID(NonZeroU64::new(provenance).unwrap()),
+        };
+        let state = build_state(vec![
+            merge(10, 2, 3),
+            merge(11, 4, 5),
+            trigger_info(12, 1, 6, 5),
+            external(20, 1, 1),
+            external(21, 1, 2),
+        ]);
+
+        assert_eq!(
+            state.events_of_kind(EventEntryKind::MergeEvent),
+            vec![event(10), event(11)]
+        );
+        assert_eq!(
+            state.events_of_kind(EventEntryKind::TriggerEvent),
+            vec![event(12)]
+        );
+        let provenance = ProvenanceID(NonZeroU64::new(1).unwrap());
+        assert_eq!(
+            state.events_of_kind(EventEntryKind::ExternalEvent(provenance)),
+            vec![event(20)]
+        );
+        assert_eq!(
+            state.events_of_kind_any_provenance(EventEntryKind::ExternalEvent(provenance)),
+            vec![event(20), event(21)]
+        );
+    }
+
+    #[test]
+    fn test_utilization_series() {
+        let p0 = proc_on(0, 1);
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 100, 1),
+            Record::TaskWaitInfo {
+                op_id: op(1),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                wait_start: ts(20),
+                wait_ready: ts(50),
+                wait_end: ts(50),
+                wait_event: event(2),
+            },
+        ]);
+        state.sort_time_range();
+        let proc = &state.procs[&p0];
+
+        assert_eq!(
+            proc.utilization_series(None, CountWaitsAs::Idle),
+            vec![(ts(0), 1.0), (ts(20), 0.0), (ts(50), 1.0), (ts(100), 0.0)]
+        );
+        assert_eq!(
+            proc.utilization_series(None, CountWaitsAs::Busy),
+            vec![(ts(0), 1.0), (ts(100), 0.0)]
+        );
+    }
+
+    #[test]
+    fn test_active_nodes() {
+        let (p0, p1) = (proc_on(0, 1), proc_on(1, 1));
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            Record::MemDesc {
+                mem_id: mem_on(1, 1),
+                kind: MemKind::System as i32,
+                capacity: 1 << 30,
+            },
+            task_info(1, p0, 0, 10, 1),
+        ]);
+        assert_eq!(state.active_nodes(), vec![NodeID(0)]);
+    }
+
+    #[test]
+    fn test_entry_kind_counts() {
+        let p0 = proc_on(0, 1);
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            Record::MapperCallDesc {
+                kind: MapperCallKindID(3),
+                name: "map_task".to_owned(),
+            },
+            task_info(1, p0, 0, 100, 1),
+            Record::MapperCallInfo {
+                mapper_id: MapperID(0),
+                mapper_proc: p0,
+                kind: MapperCallKindID(3),
+                op_id: op(1),
+                start: ts(110),
+                stop: ts(120),
+                proc_id: p0,
+                fevent: None,
+            },
+        ]);
+
+        let counts = state.procs[&p0].entry_kind_counts();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["Task"], 1);
+        assert_eq!(counts["MapperCall"], 1);
+    }
+
+    #[test]
+    fn test_op_launch_latency() {
+        let p0 = proc_on(0, 1);
+        let mut task = task_info(1, p0, 50, 60, 1);
+        if let Record::TaskInfo { create, ready, .. } = &mut task {
+            *create = ts(10);
+            *ready = ts(30);
+        }
+        let state = build_state(vec![proc_desc(p0, ProcKind::CPU), task]);
+        assert_eq!(state.op_launch_latency(op(1)), Some(ts(40)));
+        assert_eq!(state.op_launch_latency(op(2)), None);
+    }
+
+    #[test]
+    fn test_online_variance_merge() {
+        let values = [3.0, 7.5, 1.25, 9.0, 4.0, 4.0, 12.5, 0.5];
+        let mut sequential = OnlineVariance::default();
+        for v in values {
+            sequential.add(v);
+        }
+        for split in 0..=values.len() {
+            let (mut left, mut right) = (OnlineVariance::default(), OnlineVariance::default());
+            values[..split].iter().for_each(|v| left.add(*v));
+            values[split..].iter().for_each(|v| right.add(*v));
+            left.merge(&right);
+            assert_eq!(left.count, sequential.count);
+            assert!((left.mean - sequential.mean).abs() < 1e-9);
+            assert!((left.stddev() - sequential.stddev()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_message_latency_stats_across_procs() {
+        let (p0, p1, p2) = (proc_on(0, 1), proc_on(1, 1), proc_on(1, 2));
+        let mut records = vec![
+            Record::MetaDesc {
+                kind: VariantID(1),
+                message: true,
+                ordered_vc: false,
+                name: "Remote Message".to_owned(),
+            },
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            proc_desc(p2, ProcKind::CPU),
+            task_info(1, p0, 0, 200_000, 1),
+        ];
+        // Messages from node 0 spread over two procs on node 1. Negative
+        // delays are skewed: the message was created before it was spawned.
+        let delays: [i64; 8] = [2_000, -1_000, 4_000, -3_000, 1_000, 5_000, -500, -4_000];
+        for (i, delay) in delays.iter().enumerate() {
+            let proc = if i % 2 == 0 { p1 } else { p2 };
+            let spawn = 10_000 * (i as u64 + 1);
+            let create = spawn.checked_add_signed(*delay).unwrap();
+            records.push(message_info(
+                1,
+                proc,
+                spawn,
+                create,
+                create + 500,
+                1,
+                i as u64 + 2,
+            ));
+        }
+        let state = build_state(records);
+
+        // Same computation done sequentially over all the messages
+        let mut skew = OnlineVariance::default();
+        for delay in delays.iter().filter(|d| **d < 0) {
+            skew.add(-delay as f64);
+        }
+        let mut latency = OnlineVariance::default();
+        let mut latencies = Vec::new();
+        for delay in delays {
+            let corrected = delay + skew.mean as i64;
+            if corrected >= 0 {
+                latency.add(ts(corrected as u64).to_us());
+                latencies.push(ts(corrected as u64).to_us());
+            }
+        }
+
+        let stats = state.message_latency_stats();
+        assert_eq!(stats.total_messages, delays.len());
+        assert_eq!(stats.skewed_messages, 4);
+        assert_eq!(stats.total_skew, ts(8_500));
+        assert_eq!(stats.skew_nodes.len(), 1);
+        let nodes = stats.skew_nodes.get(&(NodeID(0), NodeID(1))).unwrap();
+        assert_eq!(nodes.count, skew.count);
+        assert!((nodes.mean - skew.mean).abs() < 1e-9);
+        assert!((nodes.stddev() - skew.stddev()).abs() < 1e-9);
+
+        let mut sorted = stats.latencies_us.clone();
+        sorted.sort_by(f64::total_cmp);
+        latencies.sort_by(f64::total_cmp);
+        assert_eq!(sorted, latencies);
+        assert!((stats.mean_latency_us - latency.mean).abs() < 1e-9);
+        assert!((stats.stddev_latency_us - latency.stddev()).abs() < 1e-9);
+        assert!((stats.max_latency_us - 7.125).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fill_targets() {
+        let (m0, m1) = (mem_on(0, 1), mem_on(1, 2));
+        let mut second = fill_info(2, 20, 30, 2);
+        if let Record::FillInfo { size, .. } = &mut second {
+            *size = 512;
+        }
+        let state = build_state(vec![
+            fill_info(1, 0, 10, 1),
+            fill_inst_info(m0, 10, 1),
+            second,
+            fill_inst_info(m0, 10, 2),
+            copy_info(3, 40, 50, 3),
+            copy_inst_info(m0, m1, 10, 11, 3),
+        ]);
+
+        let targets = state.fill_targets();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[&m0], 1536);
+    }
+
+    #[test]
+    fn test_write_folded_stacks() {
+        let p0 = proc_on(0, 1);
+        let mut records = task_kind(1, 1, "foo");
+        records.extend([
+            proc_desc(p0, ProcKind::CPU),
+            Record::RuntimeCallDesc {
+                kind: RuntimeCallKindID(1),
+                name: "bar".to_owned(),
+            },
+            task_info(1, p0, 0, 100, 1),
+            Record::RuntimeCallInfo {
+                kind: RuntimeCallKindID(1),
+                start: ts(10),
+                stop: ts(30),
+                proc_id: p0,
+                fevent: Some(event(1)),
+            },
+        ]);
+        let mut state = build_state(records);
+        state.sort_time_range();
+
+        let mut out = Vec::new();
+        state.write_folded_stacks(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" 80"));
+        assert!(!lines[0].contains(';'));
+        assert!(lines[1].ends_with(";bar 20"));
+        assert!(lines[1].starts_with(lines[0].strip_suffix(" 80").unwrap()));
+    }
+
+    #[test]
+    fn test_saturated_procs() {
+        let (p0, p1, p2) = (proc_on(0, 1), proc_on(0, 2), proc_on(0, 3));
+        let mut state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            proc_desc(p1, ProcKind::CPU),
+            proc_desc(p2, ProcKind::GPU),
+            task_info(1, p0, 0, 60, 1),
+            task_info(2, p0, 60, 100, 2),
+            task_info(3, p1, 0, 50, 3),
+            // The host task is short but its kernel keeps the GPU busy
+            Record::GPUTaskInfo {
+                op_id: op(4),
+                task_id: TaskID(1),
+                variant_id: VariantID(1),
+                proc_id: p2,
+                create: ts(0),
+                ready: ts(0),
+                start: ts(0),
+                stop: ts(10),
+                gpu_start: ts(5),
+                gpu_stop: ts(100),
+                creator: None,
+                critical: None,
+                fevent: event(4),
+            },
+        ]);
+        state.sort_time_range();
+        assert_eq!(state.saturated_procs(0.9), vec![p0, p2]);
+        assert_eq!(state.saturated_procs(0.4), vec![p0, p1, p2]);
+    }
+
+    #[test]
+    fn test_chan_entry_provenance() {
+        let (m0, m1) = (mem_on(0, 1), mem_on(1, 2));
+        let pid = ProvenanceID(NonZeroU64::new(7).unwrap());
+        let state = build_state(vec![
+            Record::Provenance {
+                pid,
+                provenance: "main.cc:42".to_owned(),
+            },
+            Record::OperationInstance {
+                op_id: op(1),
+                parent_id: None,
+                kind: 0,
+                provenance: Some(pid),
+            },
+            copy_info(1, 0, 10, 1),
+            copy_inst_info(m0, m1, 10, 11, 1),
+            copy_info(2, 20, 30, 2),
+            copy_inst_info(m0, m1, 10, 11, 2),
+        ]);
+
+        let chan_id = ChanID::new_copy(m0, m1);
+        let uids: Vec<_> = state.chans[&chan_id].entries.keys().copied().collect();
+        let provenances: Vec<_> = uids
+            .iter()
+            .map(|uid| state.chan_entry_provenance(chan_id, *uid))
+            .collect();
+        assert_eq!(provenances, vec![Some("main.cc:42"), None]);
+        assert_eq!(
+            state.chan_entry_provenance(ChanID::new_fill(m0), uids[0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_significant_tasks() {
+        let p0 = proc_on(0, 1);
+        let state = build_state(vec![
+            proc_desc(p0, ProcKind::CPU),
+            task_info(1, p0, 0, 95, 1),
+            task_info(2, p0, 95, 100, 2),
+        ]);
+        let long = state.procs[&p0].find_task(op(1)).unwrap().base.prof_uid;
+        assert_eq!(state.significant_tasks(0.1), vec![long]);
+        assert_eq!(state.significant_tasks(0.0).len(), 2);
+    }
+
+    #[test]
+    fn test_deppart_total_time() {
+        let partition = |op_id, start, stop, fevent| Record::PartitionInfo {
+            op_id: op(op_id),
+            part_op: DepPartKind::EqualPartition as i32,
+            create: ts(start),
+            ready: ts(start),
+            start: ts(start),
+            stop: ts(stop),
+            creator: None,
+            critical: None,
+            fevent: event(fevent),
+        };
+        let state = build_state(vec![
+            Record::MachineDesc {
+                node_id: NodeID(0),
+                num_nodes: 1,
+                version: 0,
+                hostname: "localhost".to_owned(),
+                host_id: 0,
+                process_id: 0,
+            },
+            partition(1, 0, 10, 1),
+            partition(2, 20, 45, 2),
+        ]);
+
+        assert_eq!(state.deppart_total_time(), ts(35));
+    }
+
+    #[test]
+    fn test_op_field_usage() {
+        let usage = |index_id, field_id| Record::PhysicalInstanceUsage {
+            fevent: event(5),
+            op_id: op(1),
+            index_id,
+            field_id: FieldID(field_id),
+        };
+        let state = build_state(vec![usage(0, 10), usage(1, 11)]);
+
+        let usage = state.op_field_usage(op(1));
+        let inst_uid = usage[0].0;
+        assert_eq!(state.find_fevent(inst_uid), event(5));
+        assert_eq!(
+            state.op_field_usage(op(1)),
+            vec![(inst_uid, FieldID(10)), (inst_uid, FieldID(11))]
+        );
+        let infos = &state.find_op(op(1)).unwrap().operation_inst_infos;
+        assert_eq!(infos[1].index(), 1);
+        assert_eq!(infos[1].field_id(), FieldID(11));
+        assert_eq!(state.op_field_usage(op(2)), vec![]);
+    }
 }